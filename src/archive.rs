@@ -0,0 +1,250 @@
+use crate::compression::{decode, strip_compressed_suffix, InputReader};
+use anyhow::{anyhow, Context, Result};
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ArchiveKind {
+    Zip,
+    Tar,
+}
+
+pub fn archive_kind(path: &Path) -> Option<ArchiveKind> {
+    let name = path.file_name()?.to_str()?.to_ascii_lowercase();
+    if name.ends_with(".zip") {
+        return Some(ArchiveKind::Zip);
+    }
+    if name.ends_with(".tgz") {
+        return Some(ArchiveKind::Tar);
+    }
+    match strip_compressed_suffix(Path::new(&name))
+        .extension()
+        .and_then(|e| e.to_str())
+    {
+        Some("tar") => Some(ArchiveKind::Tar),
+        _ => None,
+    }
+}
+
+pub fn member_display_path(archive: &Path, member: &str) -> PathBuf {
+    PathBuf::from(format!("{}!/{}", archive.display(), member))
+}
+
+// `index` é a posição da entrada no pacote (no índice do zip, na sequência do tar): nomes
+// podem se repetir num tar, posições não. `size` é o tamanho descomprimido do membro.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveMember {
+    pub index: usize,
+    pub name: String,
+    pub size: u64,
+}
+
+pub fn list_members(path: &Path, kind: ArchiveKind) -> Result<Vec<ArchiveMember>> {
+    let file = File::open(path).with_context(|| format!("Falha ao abrir arquivo {:?}", path))?;
+    let mut members = Vec::new();
+
+    match kind {
+        ArchiveKind::Zip => {
+            let mut archive = zip::ZipArchive::new(BufReader::new(file))
+                .with_context(|| format!("Arquivo zip inválido: {:?}", path))?;
+            for idx in 0..archive.len() {
+                let entry = archive
+                    .by_index_raw(idx)
+                    .with_context(|| format!("Falha ao ler índice do zip {:?}", path))?;
+                if entry.is_file() {
+                    members.push(ArchiveMember {
+                        index: idx,
+                        name: entry.name().to_string(),
+                        size: entry.size(),
+                    });
+                }
+            }
+        }
+        ArchiveKind::Tar => {
+            let stream = decode(BufReader::new(file))
+                .with_context(|| format!("Falha ao ler cabeçalho de {:?}", path))?;
+            let mut archive = tar::Archive::new(stream);
+            let entries = archive
+                .entries()
+                .with_context(|| format!("Arquivo tar inválido: {:?}", path))?;
+            for (index, entry) in entries.enumerate() {
+                let entry = entry.with_context(|| format!("Falha ao percorrer tar {:?}", path))?;
+                if entry.header().entry_type().is_file() {
+                    members.push(ArchiveMember {
+                        index,
+                        name: String::from_utf8_lossy(&entry.path_bytes()).into_owned(),
+                        size: entry.size(),
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(members)
+}
+
+// Lê os membros nas posições `wanted` (as de `ArchiveMember::index`), chamando `f` com a
+// posição, o nome e o conteúdo de cada um.
+pub fn for_each_member<F>(path: &Path, kind: ArchiveKind, wanted: &[usize], mut f: F) -> Result<()>
+where
+    F: FnMut(usize, &str, InputReader<'_>) -> Result<()>,
+{
+    let file = File::open(path).with_context(|| format!("Falha ao abrir arquivo {:?}", path))?;
+
+    match kind {
+        ArchiveKind::Zip => {
+            let mut archive = zip::ZipArchive::new(BufReader::new(file))
+                .with_context(|| format!("Arquivo zip inválido: {:?}", path))?;
+            for &index in wanted {
+                let entry = archive
+                    .by_index(index)
+                    .with_context(|| format!("Membro {} não encontrado em {:?}", index, path))?;
+                let name = entry.name().to_string();
+                let size = entry.size();
+                let reader = InputReader::from_reader(entry, size).with_context(|| {
+                    format!("Falha ao ler {:?}", member_display_path(path, &name))
+                })?;
+                f(index, &name, reader)?;
+            }
+        }
+        ArchiveKind::Tar => {
+            // Tar não tem índice: os membros são lidos na ordem em que aparecem no fluxo.
+            let mut pending: HashSet<usize> = wanted.iter().copied().collect();
+            let stream = decode(BufReader::new(file))
+                .with_context(|| format!("Falha ao ler cabeçalho de {:?}", path))?;
+            let mut archive = tar::Archive::new(stream);
+            let entries = archive
+                .entries()
+                .with_context(|| format!("Arquivo tar inválido: {:?}", path))?;
+            for (index, entry) in entries.enumerate() {
+                if pending.is_empty() {
+                    break;
+                }
+                let entry = entry.with_context(|| format!("Falha ao percorrer tar {:?}", path))?;
+                if !entry.header().entry_type().is_file() || !pending.remove(&index) {
+                    continue;
+                }
+                let name = String::from_utf8_lossy(&entry.path_bytes()).into_owned();
+                let size = entry.size();
+                let reader = InputReader::from_reader(entry, size).with_context(|| {
+                    format!("Falha ao ler {:?}", member_display_path(path, &name))
+                })?;
+                f(index, &name, reader)?;
+            }
+            if let Some(missing) = pending.into_iter().next() {
+                return Err(anyhow!("Membro {} não encontrado em {:?}", missing, path));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lines::read_next_line;
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    fn write_tar_gz(path: &Path, members: &[(&str, &[u8])]) {
+        let enc = flate2::write::GzEncoder::new(
+            File::create(path).unwrap(),
+            flate2::Compression::default(),
+        );
+        let mut builder = tar::Builder::new(enc);
+        for (name, data) in members {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, name, *data).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap();
+    }
+
+    fn write_zip(path: &Path, members: &[(&str, &[u8])]) {
+        let mut writer = zip::ZipWriter::new(File::create(path).unwrap());
+        for (name, data) in members {
+            writer
+                .start_file(*name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(data).unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    fn read_wanted(path: &Path, wanted: &[usize]) -> Vec<(String, Vec<Vec<u8>>)> {
+        let kind = archive_kind(path).unwrap();
+        let mut seen = Vec::new();
+        for_each_member(path, kind, wanted, |_, name, mut reader| {
+            let mut lines = Vec::new();
+            while let Some(line) = read_next_line(&mut reader)? {
+                lines.push(line);
+            }
+            seen.push((name.to_string(), lines));
+            Ok(())
+        })
+        .unwrap();
+        seen
+    }
+
+    #[test]
+    fn detects_archive_kind_by_name() {
+        assert_eq!(archive_kind(Path::new("a.ZIP")), Some(ArchiveKind::Zip));
+        assert_eq!(archive_kind(Path::new("a.tar")), Some(ArchiveKind::Tar));
+        assert_eq!(archive_kind(Path::new("a.tar.gz")), Some(ArchiveKind::Tar));
+        assert_eq!(archive_kind(Path::new("a.tgz")), Some(ArchiveKind::Tar));
+        assert_eq!(archive_kind(Path::new("a.txt.gz")), None);
+    }
+
+    #[test]
+    fn streams_selected_members_of_zip_and_tar() {
+        let dir = tempdir().unwrap();
+        let members: [(&str, &[u8]); 3] = [
+            ("top.txt", b"a\nb\n"),
+            ("nested/inner.txt", b"c\n"),
+            ("skip.csv", b"x\n"),
+        ];
+
+        let zip_path = dir.path().join("bundle.zip");
+        write_zip(&zip_path, &members);
+        let tar_path = dir.path().join("bundle.tar.gz");
+        write_tar_gz(&tar_path, &members);
+
+        for path in [&zip_path, &tar_path] {
+            let kind = archive_kind(path).unwrap();
+            let listed = list_members(path, kind).unwrap();
+            let names: Vec<&str> = listed.iter().map(|m| m.name.as_str()).collect();
+            assert_eq!(names, ["top.txt", "nested/inner.txt", "skip.csv"]);
+            assert_eq!(listed[0].size, 4);
+            assert_eq!(
+                read_wanted(path, &[listed[0].index, listed[1].index]),
+                vec![
+                    ("top.txt".to_string(), vec![b"a".to_vec(), b"b".to_vec()]),
+                    ("nested/inner.txt".to_string(), vec![b"c".to_vec()]),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn reads_every_tar_member_with_a_repeated_name() {
+        let dir = tempdir().unwrap();
+        let tar_path = dir.path().join("repetido.tar.gz");
+        write_tar_gz(&tar_path, &[("a.txt", b"1\n"), ("a.txt", b"2\n")]);
+
+        let listed = list_members(&tar_path, ArchiveKind::Tar).unwrap();
+        assert_eq!(listed.len(), 2);
+        let indices: Vec<usize> = listed.iter().map(|m| m.index).collect();
+        assert_eq!(
+            read_wanted(&tar_path, &indices),
+            vec![
+                ("a.txt".to_string(), vec![b"1".to_vec()]),
+                ("a.txt".to_string(), vec![b"2".to_vec()]),
+            ]
+        );
+    }
+}
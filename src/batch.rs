@@ -0,0 +1,358 @@
+use crate::config::{is_stdio, Config};
+use crate::control::{Cancelled, RunControl};
+use crate::summary::RunSummary;
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Instant;
+
+const DEFAULTS_KEY: &str = "defaults";
+const JOBS_KEY: &str = "jobs";
+
+pub struct Batch {
+    pub jobs: Vec<Job>,
+    pub concurrency: usize,
+    pub stop_on_failure: bool,
+    pub quiet: bool,
+}
+
+pub struct Job {
+    pub name: String,
+    pub config: Config,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Ok,
+    Partial,
+    Failed,
+    Cancelled,
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JobReport {
+    pub name: String,
+    pub output: String,
+    pub status: JobStatus,
+    pub elapsed_secs: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub summary: Option<RunSummary>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BatchSummary {
+    pub jobs: Vec<JobReport>,
+    pub elapsed_secs: f64,
+}
+
+impl BatchSummary {
+    pub fn count(&self, status: JobStatus) -> usize {
+        self.jobs.iter().filter(|job| job.status == status).count()
+    }
+}
+
+impl Batch {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Não foi possível ler o arquivo de jobs {:?}", path))?;
+        Self::parse(&text).with_context(|| format!("Arquivo de jobs {:?} inválido", path))
+    }
+
+    // `[defaults]` vale para todos os jobs (varredura, temporários etc.) e cada `[[jobs]]`
+    // pode substituir qualquer chave, com o mesmo formato do arquivo de --config.
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut table: toml::Table = text.parse()?;
+        let concurrency = match table.remove("concurrency") {
+            None => 1,
+            Some(toml::Value::Integer(n)) if n > 0 => n as usize,
+            Some(_) => return Err(anyhow!("`concurrency` deve ser um inteiro positivo")),
+        };
+        let stop_on_failure = match table.remove("stop_on_failure") {
+            None => false,
+            Some(toml::Value::Boolean(stop)) => stop,
+            Some(_) => return Err(anyhow!("`stop_on_failure` deve ser true ou false")),
+        };
+        let defaults = match table.remove(DEFAULTS_KEY) {
+            None => toml::Table::new(),
+            Some(toml::Value::Table(defaults)) => defaults,
+            Some(_) => return Err(anyhow!("`{}` deve ser uma tabela", DEFAULTS_KEY)),
+        };
+        let jobs = match table.remove(JOBS_KEY) {
+            Some(toml::Value::Array(jobs)) if !jobs.is_empty() => jobs,
+            None | Some(toml::Value::Array(_)) => {
+                return Err(anyhow!("Nenhum job definido (use [[{}]])", JOBS_KEY))
+            }
+            Some(_) => return Err(anyhow!("`{}` deve ser uma lista de tabelas", JOBS_KEY)),
+        };
+        if let Some(key) = table.keys().next() {
+            return Err(anyhow!(
+                "Chave desconhecida {:?} (opções comuns aos jobs ficam em [{}])",
+                key,
+                DEFAULTS_KEY
+            ));
+        }
+
+        let mut names = HashSet::new();
+        let mut outputs = HashSet::new();
+        let mut parsed = Vec::with_capacity(jobs.len());
+        for (idx, job) in jobs.into_iter().enumerate() {
+            let toml::Value::Table(job) = job else {
+                return Err(anyhow!("O job {} não é uma tabela", idx + 1));
+            };
+            let mut values = defaults.clone();
+            values.extend(job);
+            let name = match values.remove("name") {
+                None => format!("job-{}", idx + 1),
+                Some(toml::Value::String(name)) => name,
+                Some(_) => return Err(anyhow!("`name` do job {} deve ser texto", idx + 1)),
+            };
+            let config =
+                Config::from_table(values).with_context(|| format!("Job {:?} inválido", name))?;
+
+            if config.output.as_os_str().is_empty() {
+                return Err(anyhow!("Job {:?} sem `output`", name));
+            }
+            if config.inputs.is_empty() {
+                return Err(anyhow!("Job {:?} sem `inputs`", name));
+            }
+            if is_stdio(&config.output) || config.inputs.iter().any(|p| is_stdio(p)) {
+                return Err(anyhow!(
+                    "Job {:?}: jobs em lote não usam a entrada ou a saída padrão",
+                    name
+                ));
+            }
+            if !names.insert(name.clone()) {
+                return Err(anyhow!("Nome de job repetido: {:?}", name));
+            }
+            if !outputs.insert(config.output.clone()) {
+                return Err(anyhow!(
+                    "Job {:?} usa a mesma saída {:?} de outro job",
+                    name,
+                    config.output
+                ));
+            }
+            parsed.push(Job { name, config });
+        }
+
+        Ok(Self {
+            jobs: parsed,
+            concurrency,
+            stop_on_failure,
+            quiet: false,
+        })
+    }
+}
+
+// Executa os jobs na ordem do arquivo, até `concurrency` ao mesmo tempo. Falhas de um job
+// ficam no relatório dele; com `stop_on_failure` nenhum job novo começa depois da primeira,
+// e os que não chegaram a rodar aparecem como `skipped`.
+pub fn run(batch: &Batch, control: &RunControl) -> BatchSummary {
+    let started = Instant::now();
+    let next = AtomicUsize::new(0);
+    let stop = AtomicBool::new(false);
+    let reports: Mutex<Vec<Option<JobReport>>> = Mutex::new(vec![None; batch.jobs.len()]);
+    let workers = batch.concurrency.clamp(1, batch.jobs.len().max(1));
+
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                if stop.load(Ordering::SeqCst) || control.cancellation().is_cancelled() {
+                    break;
+                }
+                let idx = next.fetch_add(1, Ordering::SeqCst);
+                let Some(job) = batch.jobs.get(idx) else {
+                    break;
+                };
+                let report = run_job(batch, idx, job, control);
+                if batch.stop_on_failure && report.status == JobStatus::Failed {
+                    stop.store(true, Ordering::SeqCst);
+                }
+                reports.lock().unwrap()[idx] = Some(report);
+            });
+        }
+    });
+
+    let jobs = reports
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .zip(&batch.jobs)
+        .map(|(report, job)| {
+            report.unwrap_or_else(|| JobReport {
+                name: job.name.clone(),
+                output: job.config.output.display().to_string(),
+                status: JobStatus::Skipped,
+                elapsed_secs: 0.0,
+                error: None,
+                summary: None,
+            })
+        })
+        .collect();
+    BatchSummary {
+        jobs,
+        elapsed_secs: started.elapsed().as_secs_f64(),
+    }
+}
+
+fn run_job(batch: &Batch, idx: usize, job: &Job, control: &RunControl) -> JobReport {
+    let mut config = job.config.clone();
+    // Com jobs simultâneos o progresso de cada um se misturaria no terminal.
+    if batch.quiet || batch.concurrency > 1 {
+        config.quiet = true;
+    }
+    if !batch.quiet {
+        eprintln!(
+            "[{}] Iniciando job {}/{}",
+            job.name,
+            idx + 1,
+            batch.jobs.len()
+        );
+    }
+
+    let started = Instant::now();
+    let (status, error, summary) = match crate::run(config, control) {
+        Ok(summary) if !summary.failed_inputs.is_empty() => {
+            (JobStatus::Partial, None, Some(summary))
+        }
+        Ok(summary) => (JobStatus::Ok, None, Some(summary)),
+        Err(err) if err.downcast_ref::<Cancelled>().is_some() => (JobStatus::Cancelled, None, None),
+        Err(err) => (JobStatus::Failed, Some(format!("{:#}", err)), None),
+    };
+    let elapsed_secs = started.elapsed().as_secs_f64();
+
+    match (&error, &summary) {
+        (Some(error), _) => eprintln!("[{}] Falhou: {}", job.name, error),
+        (None, Some(summary)) if !batch.quiet => eprintln!(
+            "[{}] Concluído em {:.1}s: {} linhas únicas{}",
+            job.name,
+            elapsed_secs,
+            summary.unique_lines_written,
+            match summary.failed_inputs.len() {
+                0 => String::new(),
+                failed => format!(", {} entrada(s) com falha", failed),
+            }
+        ),
+        _ => {}
+    }
+
+    JobReport {
+        name: job.name.clone(),
+        output: job.config.output.display().to_string(),
+        status,
+        elapsed_secs,
+        error,
+        summary,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn jobs_inherit_defaults_and_reject_shared_outputs() {
+        let batch = Batch::parse(
+            r#"
+concurrency = 2
+
+[defaults]
+extensions = ["txt", "csv"]
+temp_dirs = ["/tmp/ulp"]
+chunk_lines = 100
+
+[[jobs]]
+name = "clientes"
+output = "clientes.txt"
+inputs = ["dados/clientes"]
+
+[[jobs]]
+output = "fornecedores.txt"
+inputs = ["dados/fornecedores"]
+chunk_lines = 5
+"#,
+        )
+        .unwrap();
+        assert_eq!(batch.concurrency, 2);
+        assert!(!batch.stop_on_failure);
+        assert_eq!(batch.jobs[0].name, "clientes");
+        assert_eq!(batch.jobs[1].name, "job-2");
+        assert_eq!(batch.jobs[0].config.chunk_lines, 100);
+        assert_eq!(batch.jobs[1].config.chunk_lines, 5);
+        assert_eq!(batch.jobs[1].config.extensions, ["txt", "csv"]);
+        assert_eq!(batch.jobs[1].config.temp_dirs, [Path::new("/tmp/ulp")]);
+
+        let err = Batch::parse(
+            r#"
+[[jobs]]
+output = "a.txt"
+inputs = ["x"]
+
+[[jobs]]
+output = "a.txt"
+inputs = ["y"]
+"#,
+        )
+        .err()
+        .unwrap();
+        assert!(err.to_string().contains("mesma saída"));
+        assert!(
+            Batch::parse("chunk_lines = 5\n[[jobs]]\noutput = \"a\"\ninputs = [\"b\"]").is_err()
+        );
+    }
+
+    #[test]
+    fn stop_on_failure_skips_remaining_jobs() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "b\na\n").unwrap();
+        let text = format!(
+            r#"
+[defaults]
+quiet = true
+space_check = false
+
+[[jobs]]
+name = "ausente"
+output = {:?}
+inputs = [{:?}]
+
+[[jobs]]
+name = "ok"
+output = {:?}
+inputs = [{:?}]
+"#,
+            dir.path().join("x.out"),
+            dir.path().join("nao-existe"),
+            dir.path().join("ok.out"),
+            dir.path().join("a.txt"),
+        );
+        let control = RunControl::new();
+
+        let mut batch = Batch::parse(&text).unwrap();
+        batch.quiet = true;
+        batch.stop_on_failure = true;
+        let summary = run(&batch, &control);
+        assert_eq!(summary.jobs[0].status, JobStatus::Failed);
+        assert!(summary.jobs[0].error.is_some());
+        assert_eq!(summary.jobs[1].status, JobStatus::Skipped);
+        assert!(!dir.path().join("ok.out").exists());
+
+        batch.stop_on_failure = false;
+        let summary = run(&batch, &control);
+        assert_eq!(summary.count(JobStatus::Failed), 1);
+        assert_eq!(summary.jobs[1].status, JobStatus::Ok);
+        assert_eq!(
+            fs::read_to_string(dir.path().join("ok.out")).unwrap(),
+            "a\nb\n"
+        );
+    }
+}
@@ -5,7 +5,7 @@ use std::path::PathBuf;
 use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
 use std::thread;
 use ulp_merge::progress::ProgressSink;
-use ulp_merge::Config;
+use ulp_merge::{Config, OverwritePolicy, RunControl, RunSummary};
 
 fn main() -> eframe::Result<()> {
     let options = eframe::NativeOptions::default();
@@ -20,17 +20,29 @@ struct MergeGui {
     inputs: Vec<String>,
     output: String,
     ext: String,
+    include: String,
+    exclude: String,
+    skip_hidden: bool,
     recursive: bool,
     chunk_lines: String,
     temp_dir: String,
     quiet: bool,
+    overwrite: bool,
+    track_sources: bool,
+    // Opções sem campo na janela, preservadas ao carregar e salvar arquivos de configuração.
+    base: Config,
     status: String,
     logs: Vec<String>,
     processing: bool,
+    control: Option<RunControl>,
+    paused: bool,
+    close_after_cancel: bool,
     receiver: Option<Receiver<WorkerMessage>>,
     file_dialog: Option<Promise<Option<Vec<PathBuf>>>>,
     folder_dialog: Option<Promise<Option<PathBuf>>>,
     save_dialog: Option<Promise<Option<PathBuf>>>,
+    config_open_dialog: Option<Promise<Option<PathBuf>>>,
+    config_save_dialog: Option<Promise<Option<PathBuf>>>,
 }
 
 impl Default for MergeGui {
@@ -39,24 +51,41 @@ impl Default for MergeGui {
             inputs: Vec::new(),
             output: String::new(),
             ext: "txt".into(),
+            include: String::new(),
+            exclude: String::new(),
+            skip_hidden: false,
             recursive: false,
             chunk_lines: "1000000".into(),
             temp_dir: String::new(),
             quiet: false,
+            overwrite: false,
+            track_sources: false,
+            base: Config::default(),
             status: "Pronto.".into(),
             logs: Vec::new(),
             processing: false,
+            control: None,
+            paused: false,
+            close_after_cancel: false,
             receiver: None,
             file_dialog: None,
             folder_dialog: None,
             save_dialog: None,
+            config_open_dialog: None,
+            config_save_dialog: None,
         }
     }
 }
 
 enum WorkerMessage {
     Log(String),
-    Finished(Result<(), String>),
+    Paused(bool),
+    Finished(Result<Box<RunSummary>, WorkerError>),
+}
+
+enum WorkerError {
+    Cancelled,
+    Failed(String),
 }
 
 impl MergeGui {
@@ -66,6 +95,14 @@ impl MergeGui {
             loop {
                 match rx.try_recv() {
                     Ok(msg) => match msg {
+                        WorkerMessage::Paused(paused) => {
+                            self.paused = paused;
+                            self.status = if paused {
+                                "Pausado.".into()
+                            } else {
+                                "Processando...".into()
+                            };
+                        }
                         WorkerMessage::Log(line) => {
                             self.logs.push(line);
                             const MAX_LOGS: usize = 500;
@@ -76,9 +113,31 @@ impl MergeGui {
                         }
                         WorkerMessage::Finished(result) => {
                             self.processing = false;
+                            self.control = None;
+                            self.paused = false;
                             self.status = match result {
-                                Ok(()) => "Processamento concluído com sucesso.".into(),
-                                Err(err) => format!("Erro: {err}"),
+                                Ok(summary) => {
+                                    for source in &summary.sources {
+                                        self.logs.push(format!(
+                                            "{}: {} lida(s), {} única(s) no arquivo, {} exclusiva(s)",
+                                            source.source,
+                                            source.lines_read,
+                                            source.unique_lines,
+                                            source.exclusive_lines
+                                        ));
+                                    }
+                                    format!(
+                                        "Processamento concluído com sucesso. {} linha(s) única(s) gravada(s), {} duplicada(s) removida(s) em {:.1}s.",
+                                        summary.unique_lines_written,
+                                        summary.duplicates_removed,
+                                        summary.timings.total_secs
+                                    )
+                                }
+                                Err(WorkerError::Cancelled) => {
+                                    "Processamento cancelado. Arquivos temporários removidos."
+                                        .into()
+                                }
+                                Err(WorkerError::Failed(err)) => format!("Erro: {err}"),
                             };
                             disconnect = true;
                             break;
@@ -101,25 +160,61 @@ impl MergeGui {
     }
 
     fn poll_dialogs(&mut self) {
-        if let Some(result) = ready_result(&mut self.file_dialog) {
-            if let Some(paths) = result {
-                for path in paths {
-                    self.add_input_path(path);
-                }
+        if let Some(Some(paths)) = ready_result(&mut self.file_dialog) {
+            for path in paths {
+                self.add_input_path(path);
             }
         }
 
-        if let Some(result) = ready_result(&mut self.folder_dialog) {
-            if let Some(path) = result {
-                self.add_input_path(path);
-            }
+        if let Some(Some(path)) = ready_result(&mut self.folder_dialog) {
+            self.add_input_path(path);
+        }
+
+        if let Some(Some(path)) = ready_result(&mut self.save_dialog) {
+            self.output = path.display().to_string();
         }
 
-        if let Some(result) = ready_result(&mut self.save_dialog) {
-            if let Some(path) = result {
-                self.output = path.display().to_string();
+        if let Some(Some(path)) = ready_result(&mut self.config_open_dialog) {
+            match Config::from_toml_file(&path, None) {
+                Ok(config) => {
+                    self.load_fields(config);
+                    self.status = format!("Configuração carregada de {}", path.display());
+                }
+                Err(err) => self.status = format!("{err:#}"),
             }
         }
+
+        if let Some(Some(path)) = ready_result(&mut self.config_save_dialog) {
+            self.status = match self
+                .fields_to_config()
+                .and_then(|config| config.save_toml(&path).map_err(|err| format!("{err:#}")))
+            {
+                Ok(()) => format!("Configuração salva em {}", path.display()),
+                Err(err) => err,
+            };
+        }
+    }
+
+    fn load_fields(&mut self, config: Config) {
+        let paths = |items: &[PathBuf]| {
+            items
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect::<Vec<_>>()
+        };
+        self.inputs = paths(&config.inputs);
+        self.output = config.output.display().to_string();
+        self.ext = config.extensions.join(",");
+        self.include = config.include.join(";");
+        self.exclude = config.exclude.join(";");
+        self.skip_hidden = config.skip_hidden;
+        self.recursive = config.recursive;
+        self.chunk_lines = config.chunk_lines.to_string();
+        self.temp_dir = paths(&config.temp_dirs).join(";");
+        self.quiet = config.quiet;
+        self.overwrite = config.overwrite == OverwritePolicy::Force;
+        self.track_sources = config.track_sources;
+        self.base = config;
     }
 
     fn add_input_path(&mut self, path: PathBuf) {
@@ -127,6 +222,17 @@ impl MergeGui {
     }
 
     fn build_config(&self) -> Result<Config, String> {
+        let config = self.fields_to_config()?;
+        if config.inputs.is_empty() {
+            return Err("Adicione pelo menos um arquivo ou diretório de entrada.".into());
+        }
+        if config.output.as_os_str().is_empty() {
+            return Err("Informe o caminho do arquivo de saída.".into());
+        }
+        Ok(config)
+    }
+
+    fn fields_to_config(&self) -> Result<Config, String> {
         let inputs: Vec<PathBuf> = self
             .inputs
             .iter()
@@ -135,15 +241,6 @@ impl MergeGui {
             .map(PathBuf::from)
             .collect();
 
-        if inputs.is_empty() {
-            return Err("Adicione pelo menos um arquivo ou diretório de entrada.".into());
-        }
-
-        let output = self.output.trim();
-        if output.is_empty() {
-            return Err("Informe o caminho do arquivo de saída.".into());
-        }
-
         let chunk_lines = self
             .chunk_lines
             .trim()
@@ -153,32 +250,59 @@ impl MergeGui {
             return Err("Linhas por chunk deve ser maior que zero.".into());
         }
 
-        let ext = {
-            let trimmed = self.ext.trim();
-            if trimmed.is_empty() {
-                "txt".to_string()
-            } else {
-                trimmed.to_string()
-            }
-        };
+        let mut extensions: Vec<String> = self
+            .ext
+            .split(',')
+            .map(str::trim)
+            .filter(|ext| !ext.is_empty())
+            .map(String::from)
+            .collect();
+        if extensions.is_empty() {
+            extensions.push("txt".into());
+        }
 
-        let temp_dir = {
-            let trimmed = self.temp_dir.trim();
-            if trimmed.is_empty() {
-                None
-            } else {
-                Some(PathBuf::from(trimmed))
-            }
-        };
+        let include: Vec<String> = self
+            .include
+            .split(';')
+            .map(str::trim)
+            .filter(|pattern| !pattern.is_empty())
+            .map(String::from)
+            .collect();
+
+        let exclude: Vec<String> = self
+            .exclude
+            .split(';')
+            .map(str::trim)
+            .filter(|pattern| !pattern.is_empty())
+            .map(String::from)
+            .collect();
+
+        let temp_dirs: Vec<PathBuf> = self
+            .temp_dir
+            .split(';')
+            .map(str::trim)
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .collect();
 
         Ok(Config {
-            output: PathBuf::from(output),
+            output: PathBuf::from(self.output.trim()),
             inputs,
-            ext,
+            extensions,
+            include,
+            exclude,
+            skip_hidden: self.skip_hidden,
             recursive: self.recursive,
             chunk_lines,
-            temp_dir,
+            temp_dirs,
             quiet: self.quiet,
+            overwrite: if self.overwrite {
+                OverwritePolicy::Force
+            } else {
+                OverwritePolicy::NoClobber
+            },
+            track_sources: self.track_sources,
+            ..self.base.clone()
         })
     }
 
@@ -202,13 +326,42 @@ impl MergeGui {
         self.status = "Processando...".into();
         self.processing = true;
 
+        let control = RunControl::new();
+        self.control = Some(control.clone());
+
         thread::spawn(move || {
             let mut progress = GuiProgress::new(tx.clone());
-            let result =
-                ulp_merge::run_with_progress(config, &mut progress).map_err(|err| err.to_string());
+            let result = ulp_merge::run_with_progress(config, &mut progress, &control)
+                .map(Box::new)
+                .map_err(|err| {
+                    if err.downcast_ref::<ulp_merge::Cancelled>().is_some() {
+                        WorkerError::Cancelled
+                    } else {
+                        WorkerError::Failed(err.to_string())
+                    }
+                });
             let _ = tx.send(WorkerMessage::Finished(result));
         });
     }
+
+    fn cancel_processing(&mut self) {
+        if let Some(control) = self.control.as_ref() {
+            control.cancellation().cancel();
+            self.status = "Cancelando...".into();
+        }
+    }
+
+    fn toggle_pause(&mut self) {
+        if let Some(control) = self.control.as_ref() {
+            let pause = control.pause_token();
+            if pause.is_paused() {
+                pause.resume();
+            } else {
+                pause.pause();
+                self.status = "Pausando...".into();
+            }
+        }
+    }
 }
 
 impl eframe::App for MergeGui {
@@ -216,6 +369,17 @@ impl eframe::App for MergeGui {
         self.poll_worker();
         self.poll_dialogs();
 
+        // Fechar a janela no meio do processamento cancela o job e espera a limpeza dos
+        // temporários antes de encerrar.
+        if ctx.input(|i| i.viewport().close_requested()) && self.processing {
+            self.cancel_processing();
+            self.close_after_cancel = true;
+            ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+        }
+        if self.close_after_cancel && !self.processing {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+        }
+
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.heading("ULP Merge");
             ui.label("Configure os arquivos de entrada e saída para iniciar a mescla.");
@@ -256,19 +420,32 @@ impl eframe::App for MergeGui {
             });
 
             ui.horizontal(|ui| {
-                ui.label("Extensão:");
+                ui.label("Extensões (separadas por vírgula):");
                 ui.text_edit_singleline(&mut self.ext);
                 ui.label("Linhas por chunk:");
                 ui.text_edit_singleline(&mut self.chunk_lines);
             });
 
+            ui.horizontal(|ui| {
+                ui.label("Incluir apenas (glob, separados por ;):");
+                ui.text_edit_singleline(&mut self.include);
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Excluir (glob, separados por ;):");
+                ui.text_edit_singleline(&mut self.exclude);
+                ui.checkbox(&mut self.skip_hidden, "Ignorar ocultos");
+            });
+
             ui.horizontal(|ui| {
                 ui.checkbox(&mut self.recursive, "Recursivo");
                 ui.checkbox(&mut self.quiet, "Modo silencioso");
+                ui.checkbox(&mut self.overwrite, "Sobrescrever saída existente");
+                ui.checkbox(&mut self.track_sources, "Estatísticas por entrada");
             });
 
             ui.horizontal(|ui| {
-                ui.label("Diretório temporário:");
+                ui.label("Diretórios temporários (separados por ;):");
                 ui.text_edit_singleline(&mut self.temp_dir);
             });
 
@@ -280,11 +457,33 @@ impl eframe::App for MergeGui {
                 {
                     self.start_processing();
                 }
+                let pause_label = if self.paused { "Retomar" } else { "Pausar" };
+                if ui
+                    .add_enabled(self.processing, egui::Button::new(pause_label))
+                    .clicked()
+                {
+                    self.toggle_pause();
+                }
+                if ui
+                    .add_enabled(self.processing, egui::Button::new("Cancelar"))
+                    .clicked()
+                {
+                    self.cancel_processing();
+                }
                 if ui.button("Limpar logs").clicked() {
                     self.logs.clear();
                 }
             });
 
+            ui.horizontal(|ui| {
+                if ui.button("Carregar configuração").clicked() {
+                    self.config_open_dialog = Some(spawn_config_open_dialog());
+                }
+                if ui.button("Salvar configuração").clicked() {
+                    self.config_save_dialog = Some(spawn_config_save_dialog());
+                }
+            });
+
             ui.separator();
             ui.label(format!("Status: {}", self.status));
 
@@ -336,6 +535,21 @@ fn spawn_save_dialog(dir: Option<PathBuf>, file: Option<String>) -> Promise<Opti
     })
 }
 
+fn spawn_config_open_dialog() -> Promise<Option<PathBuf>> {
+    Promise::spawn_thread("config_open_dialog", || {
+        FileDialog::new().add_filter("TOML", &["toml"]).pick_file()
+    })
+}
+
+fn spawn_config_save_dialog() -> Promise<Option<PathBuf>> {
+    Promise::spawn_thread("config_save_dialog", || {
+        FileDialog::new()
+            .add_filter("TOML", &["toml"])
+            .set_file_name("ulp_merge.toml")
+            .save_file()
+    })
+}
+
 fn save_dialog_defaults(output: &str) -> (Option<PathBuf>, Option<String>) {
     let trimmed = output.trim();
     if trimmed.is_empty() {
@@ -356,11 +570,18 @@ fn save_dialog_defaults(output: &str) -> (Option<PathBuf>, Option<String>) {
 struct GuiProgress {
     tx: Sender<WorkerMessage>,
     total_lines: u64,
+    current_bytes: u64,
+    current_total: u64,
 }
 
 impl GuiProgress {
     fn new(tx: Sender<WorkerMessage>) -> Self {
-        Self { tx, total_lines: 0 }
+        Self {
+            tx,
+            total_lines: 0,
+            current_bytes: 0,
+            current_total: 0,
+        }
     }
 
     fn log(&self, msg: impl Into<String>) {
@@ -370,16 +591,33 @@ impl GuiProgress {
 
 impl ProgressSink for GuiProgress {
     fn start_file(&mut self, path: &std::path::Path) {
+        self.current_bytes = 0;
+        self.current_total = 0;
         self.log(format!("Processando {}", path.display()));
     }
 
     fn on_line(&mut self) {
         self.total_lines += 1;
-        if self.total_lines % 100_000 == 0 {
-            self.log(format!("Linhas lidas: {}", self.total_lines));
+        if self.total_lines.is_multiple_of(100_000) {
+            if self.current_total > 0 {
+                let pct = self.current_bytes.min(self.current_total) as f64
+                    / self.current_total as f64
+                    * 100.0;
+                self.log(format!(
+                    "Linhas lidas: {} ({:.1}% do arquivo atual)",
+                    self.total_lines, pct
+                ));
+            } else {
+                self.log(format!("Linhas lidas: {}", self.total_lines));
+            }
         }
     }
 
+    fn on_bytes(&mut self, consumed: u64, total: u64) {
+        self.current_bytes = consumed;
+        self.current_total = total;
+    }
+
     fn finish_file(&mut self, path: &std::path::Path) {
         self.log(format!("Concluído {}", path.display()));
     }
@@ -401,4 +639,28 @@ impl ProgressSink for GuiProgress {
     fn finish(&mut self, output: &std::path::Path) {
         self.log(format!("Resultado salvo em {}", output.display()));
     }
+
+    fn paused(&mut self) {
+        self.log("Processamento pausado.");
+        let _ = self.tx.send(WorkerMessage::Paused(true));
+    }
+
+    fn resumed(&mut self) {
+        self.log("Processamento retomado.");
+        let _ = self.tx.send(WorkerMessage::Paused(false));
+    }
+
+    fn skip_input(&mut self, path: &std::path::Path, duplicate_of: &std::path::Path) {
+        self.log(format!(
+            "Entrada ignorada: {} é duplicada de {}",
+            path.display(),
+            duplicate_of.display()
+        ));
+    }
+
+    fn fail_input(&mut self, path: &std::path::Path, error: &anyhow::Error, lines_kept: u64) {
+        self.log(ulp_merge::progress::failure_message(
+            path, error, lines_kept,
+        ));
+    }
 }
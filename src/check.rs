@@ -0,0 +1,108 @@
+use crate::compression::InputReader;
+use crate::config::is_stdio;
+use crate::lines::read_next_line;
+use anyhow::{Context, Result};
+use std::cmp::Ordering;
+use std::io::{self, BufRead};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViolationKind {
+    Duplicate,
+    OutOfOrder,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    pub kind: ViolationKind,
+    pub line_number: u64,
+    pub previous: Vec<u8>,
+    pub current: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CheckReport {
+    pub lines: u64,
+    pub duplicates: u64,
+    pub out_of_order: u64,
+    pub first_violation: Option<Violation>,
+}
+
+impl CheckReport {
+    pub fn is_ok(&self) -> bool {
+        self.first_violation.is_none()
+    }
+}
+
+pub fn check_file(path: &Path) -> Result<CheckReport> {
+    if is_stdio(path) {
+        let mut reader = InputReader::from_reader(io::stdin().lock(), 0)
+            .context("Falha ao ler da entrada padrão")?;
+        return check_reader(&mut reader).context("Erro ao ler da entrada padrão");
+    }
+
+    let mut reader = InputReader::open(path)?;
+    check_reader(&mut reader).with_context(|| format!("Erro ao ler linha em {:?}", path))
+}
+
+pub fn check_reader<R: BufRead>(reader: &mut R) -> io::Result<CheckReport> {
+    let mut report = CheckReport::default();
+    let mut previous: Option<Vec<u8>> = None;
+
+    while let Some(line) = read_next_line(reader)? {
+        report.lines += 1;
+        if let Some(prev) = previous.as_ref() {
+            let kind = match prev.as_slice().cmp(line.as_slice()) {
+                Ordering::Less => None,
+                Ordering::Equal => Some(ViolationKind::Duplicate),
+                Ordering::Greater => Some(ViolationKind::OutOfOrder),
+            };
+            if let Some(kind) = kind {
+                match kind {
+                    ViolationKind::Duplicate => report.duplicates += 1,
+                    ViolationKind::OutOfOrder => report.out_of_order += 1,
+                }
+                if report.first_violation.is_none() {
+                    report.first_violation = Some(Violation {
+                        kind,
+                        line_number: report.lines,
+                        previous: prev.clone(),
+                        current: line.clone(),
+                    });
+                }
+            }
+        }
+        previous = Some(line);
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_strictly_sorted_input() {
+        let report = check_reader(&mut &b"a\nb\nc\n"[..]).unwrap();
+        assert!(report.is_ok());
+        assert_eq!(report.lines, 3);
+    }
+
+    #[test]
+    fn reports_first_violation_and_totals() {
+        let report = check_reader(&mut &b"a\nb\nb\na\nc\nc\n"[..]).unwrap();
+        assert_eq!(report.lines, 6);
+        assert_eq!(report.duplicates, 2);
+        assert_eq!(report.out_of_order, 1);
+        assert_eq!(
+            report.first_violation,
+            Some(Violation {
+                kind: ViolationKind::Duplicate,
+                line_number: 3,
+                previous: b"b".to_vec(),
+                current: b"b".to_vec(),
+            })
+        );
+    }
+}
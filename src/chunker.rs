@@ -1,69 +1,315 @@
-use crate::lines::read_next_line;
+use crate::archive;
+use crate::compression::InputReader;
+use crate::control::RunControl;
+use crate::lines::{read_next_line, write_record, SourceId};
 use crate::progress::ProgressSink;
+use crate::scanner::{InputFile, STDIN_LABEL};
+use crate::summary::InputFailure;
 use crate::temp::TempFileFactory;
-use anyhow::{Context, Result};
-use std::fs::File;
-use std::io::{BufReader, BufWriter, Write};
-use std::path::PathBuf;
+use anyhow::{anyhow, Context, Result};
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
 use tempfile::NamedTempFile;
 
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+#[derive(Default)]
+pub struct Chunks {
+    pub temp_files: Vec<NamedTempFile>,
+    pub lines_read: u64,
+    pub bytes_in: u64,
+    pub lines_per_source: Vec<u64>,
+    pub failures: Vec<InputFailure>,
+    // Entradas abandonadas; um pacote com falha conta todos os seus membros.
+    pub failed_files: usize,
+}
+
+type Chunk = Vec<(Vec<u8>, SourceId)>;
+
+// Falhas de leitura de uma entrada podem ser toleradas (--keep-going) ou repetidas; as demais
+// (temporários, cancelamento) sempre interrompem o processamento.
+enum ReadError {
+    Input(anyhow::Error),
+    Fatal(anyhow::Error),
+}
+
 pub struct ChunkBuilder<'a> {
     max_lines: usize,
+    track_sources: bool,
     temp_factory: &'a TempFileFactory,
+    control: RunControl,
+    keep_going: bool,
+    retries: u32,
+    retry_delay: Duration,
 }
 
 impl<'a> ChunkBuilder<'a> {
-    pub fn new(max_lines: usize, temp_factory: &'a TempFileFactory) -> Self {
+    pub fn new(max_lines: usize, track_sources: bool, temp_factory: &'a TempFileFactory) -> Self {
         Self {
             max_lines,
+            track_sources,
             temp_factory,
+            control: RunControl::default(),
+            keep_going: false,
+            retries: 0,
+            retry_delay: RETRY_DELAY,
+        }
+    }
+
+    pub fn with_control(mut self, control: RunControl) -> Self {
+        self.control = control;
+        self
+    }
+
+    pub fn with_failure_policy(mut self, keep_going: bool, retries: u32) -> Self {
+        self.keep_going = keep_going;
+        self.retries = retries;
+        self
+    }
+
+    fn source_id(&self, idx: usize) -> SourceId {
+        if self.track_sources {
+            idx as SourceId
+        } else {
+            0
+        }
+    }
+
+    pub fn build(&self, files: &[InputFile], progress: &mut dyn ProgressSink) -> Result<Chunks> {
+        let mut out = Chunks::default();
+        let mut chunk: Chunk = Vec::with_capacity(self.max_lines.min(100_000));
+        self.read_files(files, 0, &mut chunk, &mut out, progress)?;
+        self.flush_chunk(&mut chunk, &mut out.temp_files)?;
+        Ok(out)
+    }
+
+    // Variante em que as entradas chegam em lotes enquanto a varredura ainda está em andamento.
+    // Os identificadores de origem seguem a ordem de chegada; a lista completa é devolvida
+    // junto com os chunks.
+    pub fn build_streaming<I>(
+        &self,
+        batches: I,
+        progress: &mut dyn ProgressSink,
+    ) -> Result<(Chunks, Vec<InputFile>)>
+    where
+        I: IntoIterator<Item = Vec<InputFile>>,
+    {
+        let mut out = Chunks::default();
+        let mut chunk: Chunk = Vec::with_capacity(self.max_lines.min(100_000));
+        let mut files = Vec::new();
+        for batch in batches {
+            let first = files.len();
+            files.extend(batch);
+            self.read_files(&files[first..], first, &mut chunk, &mut out, progress)?;
         }
+        self.flush_chunk(&mut chunk, &mut out.temp_files)?;
+        Ok((out, files))
     }
 
-    pub fn build(
+    fn read_files(
         &self,
-        files: &[PathBuf],
+        files: &[InputFile],
+        first: usize,
+        chunk: &mut Chunk,
+        out: &mut Chunks,
         progress: &mut dyn ProgressSink,
-    ) -> Result<Vec<NamedTempFile>> {
-        let mut temp_files: Vec<NamedTempFile> = Vec::new();
-        let mut chunk: Vec<Vec<u8>> = Vec::with_capacity(self.max_lines.min(100_000));
-        let mut count: usize = 0;
-
-        for path in files {
-            progress.start_file(path);
-
-            let file =
-                File::open(path).with_context(|| format!("Falha ao abrir arquivo {:?}", path))?;
-            let mut reader = BufReader::new(file);
-
-            while let Some(line) = read_next_line(&mut reader)
-                .with_context(|| format!("Erro ao ler linha em {:?}", path))?
-            {
-                chunk.push(line);
-                count += 1;
-                progress.on_line();
-
-                if count >= self.max_lines {
-                    self.flush_chunk(&mut chunk, &mut temp_files)?;
-                    count = 0;
+    ) -> Result<()> {
+        out.lines_per_source.resize(first + files.len(), 0);
+        let mut idx = 0;
+        while idx < files.len() {
+            match &files[idx] {
+                InputFile::Stdin => {
+                    let display = Path::new(STDIN_LABEL);
+                    progress.start_file(display);
+                    let read =
+                        self.with_retries(display, false, out, progress, |out, progress| {
+                            let mut reader = InputReader::from_reader(io::stdin().lock(), 0)
+                                .context("Falha ao ler da entrada padrão")
+                                .map_err(ReadError::Input)?;
+                            self.read_source(
+                                display,
+                                &mut reader,
+                                first + idx,
+                                chunk,
+                                out,
+                                progress,
+                            )
+                        })?;
+                    if read {
+                        progress.finish_file(display);
+                    } else {
+                        out.failed_files += 1;
+                    }
+                    idx += 1;
                 }
-            }
+                InputFile::Path(path) => {
+                    progress.start_file(path);
+                    // Pipes não podem ser reabertos para uma nova tentativa.
+                    let retryable = path.is_file();
+                    let read =
+                        self.with_retries(path, retryable, out, progress, |out, progress| {
+                            let mut reader = InputReader::open(path).map_err(ReadError::Input)?;
+                            self.read_source(path, &mut reader, first + idx, chunk, out, progress)
+                        })?;
+                    if read {
+                        progress.finish_file(path);
+                    } else {
+                        out.failed_files += 1;
+                    }
+                    idx += 1;
+                }
+                InputFile::Member { archive, kind, .. } => {
+                    // Membros do mesmo arquivo compactado ficam contíguos (após a ordenação ou
+                    // no mesmo lote da varredura), então o pacote é aberto uma única vez.
+                    // Chaveados pela posição no pacote: um tar pode repetir nomes.
+                    let mut members: Vec<usize> = Vec::new();
+                    let mut indices: HashMap<usize, usize> = HashMap::new();
+                    while let Some(InputFile::Member {
+                        archive: other,
+                        index,
+                        ..
+                    }) = files.get(idx)
+                    {
+                        if other != archive {
+                            break;
+                        }
+                        members.push(*index);
+                        indices.insert(*index, first + idx);
+                        idx += 1;
+                    }
 
-            progress.finish_file(path);
+                    // Uma falha vale para o pacote inteiro; numa nova tentativa, os membros
+                    // retomam a partir das linhas já aceitas.
+                    let read =
+                        self.with_retries(archive, true, out, progress, |out, progress| {
+                            let mut fatal = None;
+                            let result = archive::for_each_member(
+                                archive,
+                                *kind,
+                                &members,
+                                |member, name, mut reader| {
+                                    let display = archive::member_display_path(archive, name);
+                                    progress.start_file(&display);
+                                    match self.read_source(
+                                        &display,
+                                        &mut reader,
+                                        indices[&member],
+                                        chunk,
+                                        out,
+                                        progress,
+                                    ) {
+                                        Ok(()) => {}
+                                        Err(ReadError::Input(err)) => return Err(err),
+                                        Err(ReadError::Fatal(err)) => {
+                                            fatal = Some(err);
+                                            return Err(anyhow!("Leitura interrompida"));
+                                        }
+                                    }
+                                    progress.finish_file(&display);
+                                    Ok(())
+                                },
+                            );
+                            match (result, fatal) {
+                                (_, Some(err)) => Err(ReadError::Fatal(err)),
+                                (Err(err), None) => Err(ReadError::Input(err)),
+                                (Ok(()), None) => Ok(()),
+                            }
+                        })?;
+                    if !read {
+                        out.failed_files += members.len();
+                    }
+                }
+            }
         }
+        Ok(())
+    }
 
-        if !chunk.is_empty() {
-            self.flush_chunk(&mut chunk, &mut temp_files)?;
+    // Devolve `false` quando a entrada foi abandonada e registrada em `out.failures`.
+    fn with_retries<F>(
+        &self,
+        display: &Path,
+        retryable: bool,
+        out: &mut Chunks,
+        progress: &mut dyn ProgressSink,
+        mut attempt: F,
+    ) -> Result<bool>
+    where
+        F: FnMut(&mut Chunks, &mut dyn ProgressSink) -> Result<(), ReadError>,
+    {
+        let lines_before = out.lines_read;
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let err = match attempt(out, progress) {
+                Ok(()) => return Ok(true),
+                Err(ReadError::Fatal(err)) => return Err(err),
+                Err(ReadError::Input(err)) => err,
+            };
+            if retryable && attempts <= self.retries && is_transient(&err) {
+                progress.retry_input(display, attempts);
+                self.control
+                    .cancellation()
+                    .wait(self.retry_delay * attempts)?;
+                continue;
+            }
+            if !self.keep_going {
+                return Err(err);
+            }
+            // As linhas já lidas podem estar em chunks gravados, então ficam na saída.
+            let lines_kept = out.lines_read - lines_before;
+            progress.fail_input(display, &err, lines_kept);
+            out.failures.push(InputFailure {
+                path: display.display().to_string(),
+                reason: format!("{:#}", err),
+                attempts,
+                lines_kept,
+            });
+            return Ok(false);
         }
-
-        Ok(temp_files)
     }
 
-    fn flush_chunk(
+    // `lines_per_source[idx]` guarda quantas linhas desta entrada já foram aceitas; numa nova
+    // tentativa elas são relidas e descartadas para não contar em dobro.
+    fn read_source(
         &self,
-        chunk: &mut Vec<Vec<u8>>,
-        temp_files: &mut Vec<NamedTempFile>,
-    ) -> Result<()> {
+        path: &Path,
+        reader: &mut InputReader<'_>,
+        idx: usize,
+        chunk: &mut Chunk,
+        out: &mut Chunks,
+        progress: &mut dyn ProgressSink,
+    ) -> Result<(), ReadError> {
+        let source = self.source_id(idx);
+        let mut skip = out.lines_per_source[idx];
+        while let Some(line) = read_next_line(reader)
+            .with_context(|| format!("Erro ao ler linha em {:?}", path))
+            .map_err(ReadError::Input)?
+        {
+            self.control
+                .checkpoint(progress)
+                .map_err(ReadError::Fatal)?;
+            if skip > 0 {
+                skip -= 1;
+                continue;
+            }
+            chunk.push((line, source));
+            out.lines_read += 1;
+            out.lines_per_source[idx] += 1;
+            progress.on_line();
+            progress.on_bytes(reader.consumed(), reader.total());
+
+            if chunk.len() >= self.max_lines {
+                self.flush_chunk(chunk, &mut out.temp_files)
+                    .map_err(ReadError::Fatal)?;
+            }
+        }
+        out.bytes_in += reader.consumed();
+        Ok(())
+    }
+
+    fn flush_chunk(&self, chunk: &mut Chunk, temp_files: &mut Vec<NamedTempFile>) -> Result<()> {
         if chunk.is_empty() {
             return Ok(());
         }
@@ -71,31 +317,36 @@ impl<'a> ChunkBuilder<'a> {
         chunk.sort_unstable();
         chunk.dedup();
 
-        let mut tmp = self
-            .temp_factory
-            .create()
-            .context("Não foi possível criar arquivo temporário")?;
-        {
-            let mut writer = BufWriter::new(&mut tmp);
-            for line in chunk.iter() {
-                writer
-                    .write_all(line)
+        let tmp = self.temp_factory.write_temp(&[], |writer| {
+            for (line, source) in chunk.iter() {
+                write_record(writer, line, self.track_sources.then_some(*source))
                     .context("Erro ao escrever em arquivo temporário")?;
-                writer
-                    .write_all(b"\n")
-                    .context("Erro ao escrever quebra de linha em arquivo temporário")?;
             }
-            writer
-                .flush()
-                .context("Erro ao finalizar escrita de arquivo temporário")?;
-        }
-
+            Ok(())
+        })?;
         temp_files.push(tmp);
         chunk.clear();
         Ok(())
     }
 }
 
+// Só erros de E/S que costumam passar sozinhos (rede, disco instável) merecem nova tentativa;
+// qualquer outro se repetiria igual.
+fn is_transient(err: &anyhow::Error) -> bool {
+    err.chain()
+        .filter_map(|cause| cause.downcast_ref::<io::Error>())
+        .any(|io_err| {
+            matches!(
+                io_err.kind(),
+                io::ErrorKind::Interrupted
+                    | io::ErrorKind::TimedOut
+                    | io::ErrorKind::WouldBlock
+                    | io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::StaleNetworkFileHandle
+            )
+        })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -112,14 +363,20 @@ mod tests {
         let input = dir.path().join("input.txt");
         std::fs::write(&input, b"c\nb\na\na\n").unwrap();
         let config_output = dir.path().join("out.txt");
-        let factory = TempFileFactory::new(Some(dir.path()), &config_output).unwrap();
-        let builder = ChunkBuilder::new(2, &factory);
+        let factory = TempFileFactory::new(&[dir.path().to_path_buf()], &config_output).unwrap();
+        let builder = ChunkBuilder::new(2, false, &factory);
         let mut progress = NoopProgress;
-        let chunks = builder.build(&[input], &mut progress).unwrap();
-        assert_eq!(chunks.len(), 2);
+        let chunks = builder
+            .build(&[InputFile::Path(input)], &mut progress)
+            .unwrap();
+        assert_eq!(chunks.temp_files.len(), 2);
+        assert_eq!(chunks.lines_read, 4);
+        assert_eq!(chunks.bytes_in, 8);
+        assert_eq!(chunks.lines_per_source, vec![4]);
+        assert_eq!(factory.peak_usage(), 6);
 
         let mut contents = Vec::new();
-        for tmp in chunks {
+        for tmp in chunks.temp_files {
             let mut file = tmp.reopen().unwrap();
             let mut data = String::new();
             file.read_to_string(&mut data).unwrap();
@@ -129,4 +386,157 @@ mod tests {
         assert_eq!(contents[0], "a\n");
         assert_eq!(contents[1], "b\nc\n");
     }
+
+    #[test]
+    fn tags_streamed_batches_in_arrival_order() {
+        let dir = tempdir().unwrap();
+        let first = dir.path().join("b.txt");
+        let second = dir.path().join("a.txt");
+        std::fs::write(&first, b"x\ny\n").unwrap();
+        std::fs::write(&second, b"x\n").unwrap();
+        let factory =
+            TempFileFactory::new(&[dir.path().to_path_buf()], &dir.path().join("out.txt")).unwrap();
+        let builder = ChunkBuilder::new(10, true, &factory);
+        let batches = vec![
+            vec![InputFile::Path(first.clone())],
+            vec![InputFile::Path(second.clone())],
+        ];
+        let (chunks, files) = builder.build_streaming(batches, &mut NoopProgress).unwrap();
+        assert_eq!(files, vec![InputFile::Path(first), InputFile::Path(second)]);
+        assert_eq!(chunks.lines_per_source, vec![2, 1]);
+        assert_eq!(chunks.temp_files.len(), 1);
+
+        let mut data = String::new();
+        chunks.temp_files[0]
+            .reopen()
+            .unwrap()
+            .read_to_string(&mut data)
+            .unwrap();
+        assert_eq!(data, "x\t0\nx\t1\ny\t0\n");
+    }
+
+    #[test]
+    fn stops_when_cancelled() {
+        let dir = tempdir().unwrap();
+        let input = dir.path().join("input.txt");
+        std::fs::write(&input, b"a\nb\n").unwrap();
+        let factory =
+            TempFileFactory::new(&[dir.path().to_path_buf()], &dir.path().join("out.txt")).unwrap();
+        let control = RunControl::new();
+        control.cancellation().cancel();
+        let builder = ChunkBuilder::new(1, false, &factory).with_control(control);
+        let Err(err) = builder.build(&[InputFile::Path(input)], &mut NoopProgress) else {
+            panic!("o cancelamento deveria interromper a leitura");
+        };
+        assert!(err.downcast_ref::<crate::control::Cancelled>().is_some());
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn keep_going_records_failed_inputs() {
+        let dir = tempdir().unwrap();
+        let good = dir.path().join("good.txt");
+        let missing = dir.path().join("missing.txt");
+        let corrupt = dir.path().join("corrupt.txt.gz");
+        std::fs::write(&good, b"a\nb\n").unwrap();
+        std::fs::write(&corrupt, b"\x1f\x8b\x08\x00lixo").unwrap();
+        let files = vec![
+            InputFile::Path(missing.clone()),
+            InputFile::Path(good),
+            InputFile::Path(corrupt),
+        ];
+        let factory =
+            TempFileFactory::new(&[dir.path().to_path_buf()], &dir.path().join("out.txt")).unwrap();
+
+        let strict = ChunkBuilder::new(10, false, &factory);
+        assert!(strict.build(&files, &mut NoopProgress).is_err());
+
+        let builder = ChunkBuilder::new(10, false, &factory).with_failure_policy(true, 2);
+        let chunks = builder.build(&files, &mut NoopProgress).unwrap();
+        assert_eq!(chunks.lines_read, 2);
+        assert_eq!(chunks.lines_per_source, vec![0, 2, 0]);
+        let failed: Vec<&str> = chunks.failures.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(failed.len(), 2);
+        assert_eq!(chunks.failed_files, 2);
+        assert_eq!(failed[0], missing.display().to_string());
+        assert!(failed[1].ends_with("corrupt.txt.gz"));
+        // Nem arquivo inexistente nem conteúdo inválido justificam uma nova tentativa.
+        assert!(chunks.failures.iter().all(|f| f.attempts == 1));
+    }
+
+    #[test]
+    fn retries_only_transient_errors() {
+        let transient = anyhow::Error::new(io::Error::from(io::ErrorKind::TimedOut)).context("x");
+        assert!(is_transient(&transient));
+        assert!(!is_transient(&anyhow::Error::new(io::Error::from(
+            io::ErrorKind::NotFound
+        ))));
+        assert!(!is_transient(&anyhow!("sem erro de E/S")));
+        assert!(!is_transient(&anyhow::Error::new(io::Error::other("x"))));
+    }
+
+    // Entrega `data` e, enquanto `fail` estiver ligado, falha com um erro transitório no fim.
+    struct FlakyReader {
+        data: &'static [u8],
+        fail: bool,
+    }
+
+    impl Read for FlakyReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.data.is_empty() {
+                return match self.fail {
+                    true => Err(io::Error::from(io::ErrorKind::TimedOut)),
+                    false => Ok(0),
+                };
+            }
+            let n = buf.len().min(self.data.len());
+            buf[..n].copy_from_slice(&self.data[..n]);
+            self.data = &self.data[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn retry_resumes_after_lines_already_read() {
+        let dir = tempdir().unwrap();
+        let factory =
+            TempFileFactory::new(&[dir.path().to_path_buf()], &dir.path().join("out.txt")).unwrap();
+        let mut builder = ChunkBuilder::new(100, false, &factory).with_failure_policy(false, 1);
+        builder.retry_delay = Duration::ZERO;
+        let full: &[u8] = b"linha 01\nlinha 02\nlinha 03\nlinha 04\n";
+
+        let mut out = Chunks {
+            lines_per_source: vec![0],
+            ..Chunks::default()
+        };
+        let mut chunk = Chunk::new();
+        let mut attempts = 0;
+        let display = Path::new("instavel.txt");
+        let read = builder
+            .with_retries(
+                display,
+                true,
+                &mut out,
+                &mut NoopProgress,
+                |out, progress| {
+                    attempts += 1;
+                    let flaky = FlakyReader {
+                        data: if attempts == 1 { &full[..18] } else { full },
+                        fail: attempts == 1,
+                    };
+                    let mut reader = InputReader::from_reader(flaky, 0).unwrap();
+                    builder.read_source(display, &mut reader, 0, &mut chunk, out, progress)
+                },
+            )
+            .unwrap();
+        assert!(read);
+        assert_eq!(attempts, 2);
+        assert_eq!(out.lines_read, 4);
+        assert_eq!(out.lines_per_source, vec![4]);
+        let lines: Vec<&[u8]> = chunk.iter().map(|(line, _)| line.as_slice()).collect();
+        assert_eq!(
+            lines,
+            [&b"linha 01"[..], b"linha 02", b"linha 03", b"linha 04"]
+        );
+    }
 }
@@ -1,50 +1,366 @@
-use clap::Parser;
-use std::path::PathBuf;
-use ulp_merge::Config;
+use anyhow::{anyhow, Result};
+use clap::parser::ValueSource;
+use clap::{ArgMatches, Args, Parser, Subcommand, ValueEnum};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use ulp_merge::batch::Batch;
+use ulp_merge::config::{
+    is_stdio, parse_depth, parse_size, DeltaOutput, TempPlacement, TimeFilter, DEFAULT_CHUNK_LINES,
+};
+use ulp_merge::watch::{self, WatchOptions};
+use ulp_merge::{manifest, provenance, Config, OverlapGrouping, OverwritePolicy};
 
 #[derive(Parser, Debug)]
 #[command(
     name = "ulp-merge",
     version,
     about = "Mescla listas ULP em um único arquivo, removendo linhas duplicadas.",
-    arg_required_else_help = true
+    arg_required_else_help = true,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
 )]
 pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     #[arg(
         short,
         long,
         value_name = "ARQUIVO",
-        help = "Arquivo de saída que receberá os dados mesclados"
+        required_unless_present_any = ["config", "print_config"],
+        help = "Arquivo de saída que receberá os dados mesclados (`-` para a saída padrão)"
     )]
-    pub output: PathBuf,
+    pub output: Option<PathBuf>,
 
     #[arg(
         value_name = "CAMINHO",
-        help = "Arquivos ou diretórios de entrada a serem processados",
-        required = true
+        help = "Arquivos, diretórios, padrões glob ou pipes de entrada a serem processados (`-` para a entrada padrão, `@LISTA` para ler caminhos de um arquivo)",
+        required_unless_present_any = ["inputs_from", "config", "print_config"]
     )]
     pub inputs: Vec<PathBuf>,
 
+    #[arg(
+        long = "config",
+        value_name = "ARQUIVO",
+        help = "Lê as opções de um arquivo TOML; opções informadas na linha de comando têm precedência"
+    )]
+    pub config: Option<PathBuf>,
+
+    #[arg(
+        long = "profile",
+        value_name = "NOME",
+        requires = "config",
+        help = "Aplica o perfil [profiles.NOME] do arquivo de configuração"
+    )]
+    pub profile: Option<String>,
+
+    #[arg(
+        long = "print-config",
+        help = "Mostra a configuração efetiva em TOML e termina sem processar nada"
+    )]
+    pub print_config: bool,
+
+    #[arg(
+        long = "inputs-from",
+        value_name = "LISTA",
+        help = "Lê caminhos de entrada de um arquivo, um por linha (`-` para a entrada padrão; pode repetir)"
+    )]
+    pub inputs_from: Vec<PathBuf>,
+
+    #[arg(
+        short = '0',
+        long = "null",
+        help = "As listas de entradas são separadas por NUL em vez de quebra de linha (como `find -print0`)"
+    )]
+    pub null: bool,
+
+    #[command(flatten)]
+    pub common: CommonArgs,
+
+    #[arg(
+        long = "dedupe-content",
+        overrides_with = "no_dedupe_content",
+        help = "Além de caminhos para o mesmo arquivo, ignora entradas com conteúdo idêntico (compara tamanho e depois hash)"
+    )]
+    pub dedupe_content: bool,
+
+    #[arg(
+        long = "no-dedupe-content",
+        overrides_with = "dedupe_content",
+        help = "Desfaz --dedupe-content (por exemplo, vindo do arquivo de configuração)"
+    )]
+    pub no_dedupe_content: bool,
+
+    #[arg(
+        long = "scan-threads",
+        value_name = "N",
+        default_value_t = 0,
+        help = "Threads usadas na varredura de diretórios (0 = automático)"
+    )]
+    pub scan_threads: usize,
+
+    #[arg(
+        long = "stream-scan",
+        conflicts_with = "dedupe_content",
+        overrides_with = "no_stream_scan",
+        help = "Começa a ler as entradas enquanto a varredura ainda está em andamento (sem estimativa prévia de espaço)"
+    )]
+    pub stream_scan: bool,
+
+    #[arg(
+        long = "no-stream-scan",
+        overrides_with = "stream_scan",
+        help = "Desfaz --stream-scan (por exemplo, vindo do arquivo de configuração)"
+    )]
+    pub no_stream_scan: bool,
+
+    #[arg(
+        long = "keep-going",
+        overrides_with = "no_keep_going",
+        help = "Continua quando uma entrada não pode ser lida, registrando a falha (código de saída 5)"
+    )]
+    pub keep_going: bool,
+
+    #[arg(
+        long = "no-keep-going",
+        overrides_with = "keep_going",
+        help = "Desfaz --keep-going (por exemplo, vindo do arquivo de configuração)"
+    )]
+    pub no_keep_going: bool,
+
+    #[arg(
+        long = "failure-report",
+        value_name = "ARQUIVO",
+        help = "Grava as entradas que falharam e o motivo (CSV se a extensão for .csv, senão JSON)"
+    )]
+    pub failure_report: Option<PathBuf>,
+
+    #[arg(
+        long = "force",
+        short = 'f',
+        help = "Sobrescreve o arquivo de saída caso ele já exista (substitui `overwrite` do arquivo de configuração)",
+        conflicts_with = "no_clobber"
+    )]
+    pub force: bool,
+
+    #[arg(
+        long = "report",
+        value_name = "ARQUIVO",
+        help = "Grava um resumo da execução em JSON no arquivo informado"
+    )]
+    pub report: Option<PathBuf>,
+
+    #[arg(
+        long = "source-stats",
+        value_name = "ARQUIVO",
+        help = "Grava estatísticas por arquivo de entrada (CSV se a extensão for .csv, senão JSON)"
+    )]
+    pub source_stats: Option<PathBuf>,
+
+    #[arg(
+        long = "overlap",
+        value_name = "ARQUIVO",
+        help = "Grava a sobreposição de linhas entre pares de entradas (CSV se a extensão for .csv, senão JSON)"
+    )]
+    pub overlap: Option<PathBuf>,
+
+    #[arg(
+        long = "overlap-group",
+        value_enum,
+        default_value_t = OverlapGroupArg::File,
+        value_name = "AGRUPAMENTO",
+        help = "Como agrupar entradas no relatório de sobreposição"
+    )]
+    pub overlap_group: OverlapGroupArg,
+
+    #[arg(
+        long = "provenance",
+        help = "Grava um arquivo de proveniência (<saída>.prov) com os arquivos de origem de cada linha"
+    )]
+    pub provenance: bool,
+
+    #[arg(
+        long = "provenance-file",
+        value_name = "ARQUIVO",
+        help = "Caminho alternativo para o arquivo de proveniência (implica --provenance)"
+    )]
+    pub provenance_file: Option<PathBuf>,
+
+    #[arg(
+        long = "previous",
+        value_name = "ARQUIVO",
+        requires = "delta",
+        help = "Saída de uma execução anterior usada como base para o --delta"
+    )]
+    pub previous: Option<PathBuf>,
+
+    #[arg(
+        long = "delta",
+        value_name = "ARQUIVO",
+        requires = "previous",
+        help = "Grava apenas as linhas que não existiam na saída anterior (--previous)"
+    )]
+    pub delta: Option<PathBuf>,
+
+    #[arg(
+        long = "removed",
+        value_name = "ARQUIVO",
+        requires = "delta",
+        help = "Grava as linhas da saída anterior que sumiram do novo resultado"
+    )]
+    pub removed: Option<PathBuf>,
+
+    #[arg(
+        long = "dry-run",
+        help = "Lista as entradas e estima chunks, rodadas de merge, disco e memória sem gravar nada"
+    )]
+    pub dry_run: bool,
+
+    #[arg(
+        long = "no-clobber",
+        help = "Recusa sobrescrever um arquivo de saída existente (padrão)"
+    )]
+    pub no_clobber: bool,
+}
+
+// Opções de varredura, leitura, temporários e merge aceitas tanto pelo comando principal
+// quanto pelo `watch`.
+#[derive(Args, Debug)]
+pub struct CommonArgs {
     #[arg(
         short = 'e',
         long = "extension",
         alias = "ext",
         default_value = "txt",
         value_name = "EXT",
-        help = "Extensão usada para filtrar os arquivos de entrada"
+        value_delimiter = ',',
+        help = "Extensão usada para filtrar os arquivos de entrada (repita ou separe por vírgula para aceitar várias)"
+    )]
+    pub extensions: Vec<String>,
+
+    #[arg(
+        long = "include",
+        value_name = "GLOB",
+        help = "Só processa arquivos cujo caminho relativo à entrada case com o padrão (pode repetir, ex.: '**/passwords*.txt')"
+    )]
+    pub include: Vec<String>,
+
+    #[arg(
+        long = "exclude",
+        value_name = "GLOB",
+        help = "Ignora arquivos e diretórios cujo caminho relativo à entrada case com o padrão (pode repetir, ex.: '**/lixo')"
+    )]
+    pub exclude: Vec<String>,
+
+    #[arg(
+        long = "skip-hidden",
+        overrides_with = "no_skip_hidden",
+        help = "Ignora arquivos e diretórios ocultos (nome iniciado por ponto)"
+    )]
+    pub skip_hidden: bool,
+
+    #[arg(
+        long = "no-skip-hidden",
+        overrides_with = "skip_hidden",
+        help = "Desfaz --skip-hidden (por exemplo, vindo do arquivo de configuração)"
+    )]
+    pub no_skip_hidden: bool,
+
+    #[arg(
+        long = "no-ignore",
+        overrides_with = "ignore",
+        help = "Não aplica as regras dos arquivos .ulpignore encontrados na varredura"
+    )]
+    pub no_ignore: bool,
+
+    #[arg(
+        long = "ignore",
+        overrides_with = "no_ignore",
+        help = "Desfaz --no-ignore (por exemplo, vindo do arquivo de configuração)"
+    )]
+    pub ignore: bool,
+
+    #[arg(
+        long = "max-depth",
+        value_name = "N",
+        value_parser = parse_depth,
+        help = "Profundidade máxima da varredura de diretórios (1 = só o primeiro nível; implica --recursive)"
+    )]
+    pub max_depth: Option<usize>,
+
+    #[arg(
+        long = "follow-symlinks",
+        overrides_with = "no_follow_symlinks",
+        help = "Segue links simbólicos para diretórios durante a varredura (links em ciclo são ignorados)"
+    )]
+    pub follow_symlinks: bool,
+
+    #[arg(
+        long = "no-follow-symlinks",
+        overrides_with = "follow_symlinks",
+        help = "Desfaz --follow-symlinks (por exemplo, vindo do arquivo de configuração)"
+    )]
+    pub no_follow_symlinks: bool,
+
+    #[arg(
+        long = "min-size",
+        value_name = "TAMANHO",
+        value_parser = parse_size,
+        help = "Ignora arquivos menores que o tamanho informado (ex.: 10K, 1M)"
+    )]
+    pub min_size: Option<u64>,
+
+    #[arg(
+        long = "max-size",
+        value_name = "TAMANHO",
+        value_parser = parse_size,
+        help = "Ignora arquivos maiores que o tamanho informado (ex.: 5G)"
+    )]
+    pub max_size: Option<u64>,
+
+    #[arg(
+        long = "newer-than",
+        value_name = "IDADE|DATA",
+        value_parser = TimeFilter::parse,
+        help = "Só processa arquivos modificados depois do instante informado (ex.: 7d, 12h, 2024-05-01)"
+    )]
+    pub newer_than: Option<TimeFilter>,
+
+    #[arg(
+        long = "older-than",
+        value_name = "IDADE|DATA",
+        value_parser = TimeFilter::parse,
+        help = "Só processa arquivos modificados antes do instante informado (ex.: 30d, 2024-01-01)"
     )]
-    pub ext: String,
+    pub older_than: Option<TimeFilter>,
+
+    #[arg(
+        long = "retries",
+        value_name = "N",
+        default_value_t = 0,
+        help = "Novas tentativas para erros de leitura transitórios antes de desistir da entrada"
+    )]
+    pub retries: u32,
 
     #[arg(
         short,
         long,
+        overrides_with = "no_recursive",
         help = "Percorre diretórios recursivamente em busca de arquivos"
     )]
     pub recursive: bool,
 
+    #[arg(
+        long = "no-recursive",
+        overrides_with = "recursive",
+        conflicts_with = "max_depth",
+        help = "Desfaz --recursive (por exemplo, vindo do arquivo de configuração)"
+    )]
+    pub no_recursive: bool,
+
     #[arg(
         long,
-        default_value_t = 1_000_000,
+        default_value_t = DEFAULT_CHUNK_LINES,
         value_name = "LINHAS",
         help = "Quantidade de linhas por chunk antes de enviar para o merge"
     )]
@@ -53,28 +369,533 @@ pub struct Cli {
     #[arg(
         long = "temp-dir",
         value_name = "DIR",
-        help = "Diretório usado para armazenar arquivos temporários"
+        help = "Diretório usado para armazenar arquivos temporários (repita para distribuir entre vários discos)"
+    )]
+    pub temp_dirs: Vec<PathBuf>,
+
+    #[arg(
+        long = "temp-placement",
+        value_enum,
+        default_value_t = TempPlacementArg::RoundRobin,
+        value_name = "ESTRATÉGIA",
+        help = "Como escolher o diretório de cada arquivo temporário"
     )]
-    pub temp_dir: Option<PathBuf>,
+    pub temp_placement: TempPlacementArg,
+
+    #[arg(
+        long = "min-free",
+        value_name = "TAMANHO",
+        default_value = "256M",
+        value_parser = parse_size,
+        help = "Espaço livre mínimo a preservar nos discos de temporários e saída com --space-check (ex.: 500M, 2G)"
+    )]
+    pub min_free: u64,
+
+    #[arg(
+        long = "no-space-check",
+        overrides_with = "space_check",
+        help = "Desfaz --space-check (por exemplo, vindo do arquivo de configuração)"
+    )]
+    pub no_space_check: bool,
+
+    #[arg(
+        long = "space-check",
+        overrides_with = "no_space_check",
+        help = "Estima o espaço em disco necessário antes de começar e monitora os discos durante a execução"
+    )]
+    pub space_check: bool,
 
     #[arg(
         long = "quiet",
-        help = "Suprime mensagens de progresso",
-        action = clap::ArgAction::SetTrue
+        overrides_with = "no_quiet",
+        help = "Suprime mensagens de progresso"
+    )]
+    pub quiet: bool,
+
+    #[arg(
+        long = "no-quiet",
+        overrides_with = "quiet",
+        help = "Desfaz --quiet (por exemplo, vindo do arquivo de configuração)"
+    )]
+    pub no_quiet: bool,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum OverlapGroupArg {
+    #[value(help = "Cada arquivo de entrada é um grupo")]
+    File,
+    #[value(help = "Agrupa pelo diretório de primeiro nível dentro de cada entrada")]
+    TopDir,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum TempPlacementArg {
+    #[value(help = "Alterna entre os diretórios, um arquivo em cada")]
+    RoundRobin,
+    #[value(help = "Usa o diretório com mais espaço livre no momento")]
+    FreeSpace,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    #[command(
+        about = "Verifica se arquivos estão em ordem estritamente crescente e sem duplicados"
+    )]
+    Check(CheckArgs),
+    #[command(about = "Consulta de quais arquivos de entrada veio uma linha da saída")]
+    Provenance(ProvenanceArgs),
+    #[command(about = "Observa um diretório e incorpora à saída os arquivos novos em lotes")]
+    Watch(Box<WatchArgs>),
+    #[command(about = "Executa vários jobs de merge descritos em um arquivo TOML")]
+    Batch(BatchArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct WatchArgs {
+    #[arg(
+        short,
+        long,
+        value_name = "ARQUIVO",
+        help = "Arquivo de saída que acumula as linhas de todos os lotes"
+    )]
+    pub output: PathBuf,
+
+    #[arg(value_name = "DIR", help = "Diretório observado")]
+    pub dir: PathBuf,
+
+    #[command(flatten)]
+    pub common: CommonArgs,
+
+    #[arg(
+        long = "quiet-period",
+        value_name = "SEGUNDOS",
+        default_value_t = 10,
+        help = "Tempo sem atividade no diretório antes de processar um lote"
+    )]
+    pub quiet_period: u64,
+
+    #[arg(
+        long = "state",
+        value_name = "ARQUIVO",
+        help = "Arquivo com os arquivos já processados (padrão: <saída>.watch.json)"
+    )]
+    pub state: Option<PathBuf>,
+
+    #[arg(
+        long = "once",
+        help = "Processa os arquivos novos já presentes e termina, sem observar o diretório"
+    )]
+    pub once: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct BatchArgs {
+    #[arg(value_name = "ARQUIVO", help = "Arquivo TOML com os jobs")]
+    pub file: PathBuf,
+
+    #[arg(
+        short = 'j',
+        long = "jobs",
+        value_name = "N",
+        value_parser = clap::value_parser!(usize),
+        help = "Quantidade de jobs executados ao mesmo tempo (substitui `concurrency` do arquivo)"
+    )]
+    pub jobs: Option<usize>,
+
+    #[arg(
+        long = "stop-on-failure",
+        help = "Não inicia novos jobs depois da primeira falha"
+    )]
+    pub stop_on_failure: bool,
+
+    #[arg(
+        long = "continue",
+        conflicts_with = "stop_on_failure",
+        help = "Executa todos os jobs mesmo após falhas (substitui `stop_on_failure` do arquivo)"
+    )]
+    pub continue_on_failure: bool,
+
+    #[arg(
+        long = "temp-dir",
+        value_name = "DIR",
+        help = "Diretório de temporários para todos os jobs (pode repetir; substitui o do arquivo)"
+    )]
+    pub temp_dirs: Vec<PathBuf>,
+
+    #[arg(
+        long = "force",
+        short = 'f',
+        help = "Sobrescreve as saídas existentes de todos os jobs (substitui `overwrite` do arquivo)"
+    )]
+    pub force: bool,
+
+    #[arg(
+        long = "report",
+        value_name = "ARQUIVO",
+        help = "Grava o resumo combinado dos jobs em JSON"
     )]
+    pub report: Option<PathBuf>,
+
+    #[arg(long = "quiet", help = "Suprime mensagens de progresso")]
     pub quiet: bool,
 }
 
+#[derive(Args, Debug)]
+pub struct ProvenanceArgs {
+    #[arg(
+        short,
+        long,
+        value_name = "ARQUIVO",
+        help = "Arquivo de saída gerado com --provenance"
+    )]
+    pub output: PathBuf,
+
+    #[arg(
+        long = "provenance-file",
+        value_name = "ARQUIVO",
+        help = "Arquivo de proveniência (padrão: <saída>.prov)"
+    )]
+    pub provenance_file: Option<PathBuf>,
+
+    #[arg(
+        short = 'n',
+        long = "line-number",
+        help = "Interpreta LINHA como número da linha na saída (a partir de 1)"
+    )]
+    pub line_number: bool,
+
+    #[arg(value_name = "LINHA", help = "Conteúdo da linha a consultar")]
+    pub line: String,
+}
+
+#[derive(Args, Debug)]
+pub struct CheckArgs {
+    #[arg(
+        value_name = "ARQUIVO",
+        required = true,
+        help = "Arquivos a verificar (`-` para a entrada padrão)"
+    )]
+    pub files: Vec<PathBuf>,
+}
+
+impl CommonArgs {
+    // Aplica as opções sobre `config`; `given` diz quais vieram de fato da linha de comando,
+    // para que as demais não apaguem valores do arquivo de configuração.
+    fn apply(self, config: &mut Config, given: &dyn Fn(&str) -> bool) {
+        if given("extensions") {
+            config.extensions = self.extensions;
+        }
+        if given("include") {
+            config.include = self.include;
+        }
+        if given("exclude") {
+            config.exclude = self.exclude;
+        }
+        // Cada par de flags (`--x`/`--no-x`) só muda o valor quando uma delas é informada.
+        if self.skip_hidden || self.no_skip_hidden {
+            config.skip_hidden = self.skip_hidden;
+        }
+        if self.no_ignore || self.ignore {
+            config.ignore_files = self.ignore;
+        }
+        if let Some(depth) = self.max_depth {
+            config.max_depth = Some(depth);
+        }
+        if self.follow_symlinks || self.no_follow_symlinks {
+            config.follow_symlinks = self.follow_symlinks;
+        }
+        if let Some(size) = self.min_size {
+            config.min_size = Some(size);
+        }
+        if let Some(size) = self.max_size {
+            config.max_size = Some(size);
+        }
+        if let Some(time) = self.newer_than {
+            config.modified_after = Some(time);
+        }
+        if let Some(time) = self.older_than {
+            config.modified_before = Some(time);
+        }
+        if given("retries") {
+            config.read_retries = self.retries;
+        }
+        if self.recursive || self.no_recursive {
+            config.recursive = self.recursive;
+        } else if config.max_depth.is_some() {
+            config.recursive = true;
+        }
+        if given("chunk_lines") {
+            config.chunk_lines = self.chunk_lines;
+        }
+        if given("temp_dirs") {
+            config.temp_dirs = self.temp_dirs;
+        }
+        if given("temp_placement") {
+            config.temp_placement = match self.temp_placement {
+                TempPlacementArg::RoundRobin => TempPlacement::RoundRobin,
+                TempPlacementArg::FreeSpace => TempPlacement::FreeSpace,
+            };
+        }
+        if self.quiet || self.no_quiet {
+            config.quiet = self.quiet;
+        }
+        if given("min_free") {
+            config.min_free_bytes = self.min_free;
+        }
+        if self.no_space_check || self.space_check {
+            config.space_check = self.space_check;
+        }
+    }
+}
+
 impl Cli {
-    pub fn into_config(self) -> Config {
-        Config {
+    // Parte do arquivo de --config (ou dos padrões) e aplica por cima apenas as opções
+    // informadas de fato na linha de comando; `matches` diz quais foram.
+    pub fn into_config(self, matches: &ArgMatches) -> Result<Config> {
+        let mut config = match self.config.as_deref() {
+            Some(path) => Config::from_toml_file(path, self.profile.as_deref())?,
+            None => Config::default(),
+        };
+        let given = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+
+        if !self.inputs.is_empty() || !self.inputs_from.is_empty() {
+            config.inputs = self.resolve_inputs()?;
+        }
+        if let Some(output) = self.output {
+            config.output = output;
+        }
+        self.common.apply(&mut config, &given);
+        if self.dedupe_content || self.no_dedupe_content {
+            config.dedupe_content = self.dedupe_content;
+        }
+        if given("scan_threads") {
+            config.scan_threads = self.scan_threads;
+        }
+        if self.stream_scan || self.no_stream_scan {
+            config.stream_scan = self.stream_scan;
+        }
+        if self.keep_going || self.no_keep_going {
+            config.keep_going = self.keep_going;
+        }
+        if self.force {
+            config.overwrite = OverwritePolicy::Force;
+        } else if self.no_clobber {
+            config.overwrite = OverwritePolicy::NoClobber;
+        }
+        if self.source_stats.is_some() {
+            config.track_sources = true;
+        }
+        let grouping = match self.overlap_group {
+            OverlapGroupArg::File => OverlapGrouping::File,
+            OverlapGroupArg::TopDir => OverlapGrouping::TopDir,
+        };
+        if self.overlap.is_some() || (given("overlap_group") && config.overlap.is_some()) {
+            config.overlap = Some(grouping);
+        }
+        match self.provenance_file {
+            Some(path) => config.provenance = Some(path),
+            None if self.provenance => {
+                config.provenance = Some(provenance::sidecar_path(&config.output))
+            }
+            None => {}
+        }
+        if let (Some(previous), Some(added)) = (self.previous, self.delta) {
+            config.delta = Some(DeltaOutput {
+                previous,
+                added,
+                removed: self.removed,
+            });
+        }
+
+        if !self.print_config {
+            if config.output.as_os_str().is_empty() {
+                return Err(anyhow!(
+                    "Informe o arquivo de saída com -o/--output ou `output` no arquivo de configuração"
+                ));
+            }
+            if config.inputs.is_empty() {
+                return Err(anyhow!("A lista de entradas está vazia"));
+            }
+        }
+        Ok(config)
+    }
+
+    // Expande `@LISTA` e `--inputs-from` na lista final de entradas, mantendo a ordem em que
+    // foram informadas. A validação dos caminhos fica a cargo da varredura, como para os
+    // posicionais.
+    fn resolve_inputs(&self) -> Result<Vec<PathBuf>> {
+        let mut inputs = Vec::new();
+        let mut lists_from_stdin = 0;
+        for input in &self.inputs {
+            match input.to_str().and_then(|text| text.strip_prefix('@')) {
+                Some(list) => {
+                    lists_from_stdin += usize::from(is_stdio(Path::new(list)));
+                    inputs.extend(manifest::read_input_list(Path::new(list), self.null)?);
+                }
+                None => inputs.push(input.clone()),
+            }
+        }
+        for list in &self.inputs_from {
+            lists_from_stdin += usize::from(is_stdio(list));
+            inputs.extend(manifest::read_input_list(list, self.null)?);
+        }
+
+        if lists_from_stdin > 1 || (lists_from_stdin == 1 && inputs.iter().any(|p| is_stdio(p))) {
+            return Err(anyhow!(
+                "A entrada padrão só pode ser usada uma vez: como lista de entradas ou como dado"
+            ));
+        }
+        if inputs.is_empty() {
+            return Err(anyhow!("A lista de entradas está vazia"));
+        }
+        Ok(inputs)
+    }
+}
+
+impl WatchArgs {
+    pub fn into_config(self) -> (Config, WatchOptions) {
+        let options = WatchOptions {
+            quiet_period: Duration::from_secs(self.quiet_period),
+            state_file: self
+                .state
+                .unwrap_or_else(|| watch::state_path(&self.output)),
+            once: self.once,
+        };
+        // Sem arquivo de configuração, os valores da linha de comando (ou seus padrões) valem
+        // todos.
+        let mut config = Config {
             output: self.output,
-            inputs: self.inputs,
-            ext: self.ext,
-            recursive: self.recursive,
-            chunk_lines: self.chunk_lines,
-            temp_dir: self.temp_dir,
-            quiet: self.quiet,
+            inputs: vec![self.dir],
+            ..Config::default()
+        };
+        self.common.apply(&mut config, &|_| true);
+        (config, options)
+    }
+}
+
+impl BatchArgs {
+    pub fn load(&self) -> Result<Batch> {
+        let mut batch = Batch::load(&self.file)?;
+        if let Some(jobs) = self.jobs {
+            if jobs == 0 {
+                return Err(anyhow!("--jobs deve ser maior que zero"));
+            }
+            batch.concurrency = jobs;
         }
+        if self.stop_on_failure {
+            batch.stop_on_failure = true;
+        } else if self.continue_on_failure {
+            batch.stop_on_failure = false;
+        }
+        if !self.temp_dirs.is_empty() {
+            for job in &mut batch.jobs {
+                job.config.temp_dirs = self.temp_dirs.clone();
+            }
+        }
+        if self.force {
+            for job in &mut batch.jobs {
+                job.config.overwrite = OverwritePolicy::Force;
+            }
+        }
+        batch.quiet = self.quiet;
+        Ok(batch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{CommandFactory, FromArgMatches};
+
+    fn config_from(args: &[&str]) -> Config {
+        let matches = Cli::command().try_get_matches_from(args).unwrap();
+        let cli = Cli::from_arg_matches(&matches).unwrap();
+        cli.into_config(&matches).unwrap()
+    }
+
+    #[test]
+    fn command_line_turns_off_options_from_the_config_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("job.toml");
+        std::fs::write(
+            &file,
+            "output = \"saida.txt\"\ninputs = [\"dados\"]\nrecursive = true\nquiet = true\nkeep_going = true\nspace_check = true\nmodified_after = \"7d\"\n",
+        )
+        .unwrap();
+        let file = file.to_str().unwrap();
+
+        let config = config_from(&["ulp_merge", "--config", file]);
+        assert!(config.recursive && config.quiet && config.keep_going);
+        assert!(config.space_check);
+
+        let config = config_from(&[
+            "ulp_merge",
+            "--config",
+            file,
+            "--no-recursive",
+            "--no-quiet",
+            "--no-keep-going",
+            "--no-space-check",
+        ]);
+        assert!(!config.recursive && !config.quiet && !config.keep_going);
+        assert!(!config.space_check);
+        assert!(config
+            .to_toml()
+            .unwrap()
+            .contains("modified_after = \"7d\""));
+
+        // A última flag de um par prevalece.
+        let config = config_from(&["ulp_merge", "--config", file, "--no-quiet", "--quiet"]);
+        assert!(config.quiet);
+    }
+
+    #[test]
+    fn batch_force_overrides_no_clobber_jobs() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("jobs.toml");
+        std::fs::write(
+            &file,
+            "[defaults]\noverwrite = \"no-clobber\"\n\n[[jobs]]\noutput = \"a.txt\"\ninputs = [\"dados\"]\n",
+        )
+        .unwrap();
+        let load = |args: &[&str]| {
+            let Some(Command::Batch(args)) = Cli::try_parse_from(args).unwrap().command else {
+                panic!("esperava o subcomando batch");
+            };
+            args.load().unwrap()
+        };
+        let file = file.to_str().unwrap();
+
+        let batch = load(&["ulp_merge", "batch", file]);
+        assert_eq!(batch.jobs[0].config.overwrite, OverwritePolicy::NoClobber);
+        let batch = load(&["ulp_merge", "batch", "--force", file]);
+        assert_eq!(batch.jobs[0].config.overwrite, OverwritePolicy::Force);
+    }
+
+    #[test]
+    fn watch_accepts_the_shared_scan_options() {
+        let cli = Cli::try_parse_from([
+            "ulp_merge",
+            "watch",
+            "-o",
+            "acumulado.txt",
+            "--min-size",
+            "1K",
+            "--newer-than",
+            "7d",
+            "--temp-placement",
+            "free-space",
+            "/srv/entrega",
+        ])
+        .unwrap();
+        let Some(Command::Watch(args)) = cli.command else {
+            panic!("esperava o subcomando watch");
+        };
+        let (config, _) = args.into_config();
+        assert_eq!(config.inputs, [PathBuf::from("/srv/entrega")]);
+        assert_eq!(config.min_size, Some(1024));
+        assert!(config.modified_after.is_some());
+        assert_eq!(config.temp_placement, TempPlacement::FreeSpace);
+        assert_eq!(config.extensions, ["txt"]);
     }
 }
@@ -0,0 +1,331 @@
+use crate::cli::{BatchArgs, CheckArgs, ProvenanceArgs, WatchArgs};
+use anyhow::{anyhow, Context, Result};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use ulp_merge::batch::{self, BatchSummary, JobStatus};
+use ulp_merge::check::{self, ViolationKind};
+use ulp_merge::disk::format_bytes;
+use ulp_merge::plan::Plan;
+use ulp_merge::provenance::{self as prov, ProvenanceReader};
+use ulp_merge::{Cancelled, RunControl, RunSummary};
+
+pub const EXIT_DUPLICATES: u8 = 3;
+pub const EXIT_OUT_OF_ORDER: u8 = 4;
+pub const EXIT_PARTIAL: u8 = 5;
+pub const EXIT_CANCELLED: u8 = 130;
+
+pub fn check(args: CheckArgs) -> Result<ExitCode> {
+    let code = check_files(&args.files, &mut io::stdout().lock())?;
+    Ok(ExitCode::from(code))
+}
+
+// Um arquivo ilegível é informado e a verificação segue com os demais; o erro de leitura
+// prevalece sobre os outros códigos, pois o resultado daquele arquivo é desconhecido.
+fn check_files(files: &[PathBuf], out: &mut impl Write) -> Result<u8> {
+    let mut code = 0;
+    let mut read_error = false;
+
+    for path in files {
+        let report = match check::check_file(path) {
+            Ok(report) => report,
+            Err(err) => {
+                writeln!(out, "{}: ERRO: {:#}", path.display(), err)?;
+                read_error = true;
+                continue;
+            }
+        };
+        let Some(violation) = report.first_violation.as_ref() else {
+            writeln!(out, "{}: OK ({} linhas)", path.display(), report.lines)?;
+            continue;
+        };
+
+        let description = match violation.kind {
+            ViolationKind::Duplicate => "duplicada",
+            ViolationKind::OutOfOrder => "fora de ordem",
+        };
+        writeln!(
+            out,
+            "{}: FALHA na linha {}: {:?} {} após {:?}. {} duplicada(s), {} fora de ordem em {} linhas",
+            path.display(),
+            violation.line_number,
+            String::from_utf8_lossy(&violation.current),
+            description,
+            String::from_utf8_lossy(&violation.previous),
+            report.duplicates,
+            report.out_of_order,
+            report.lines
+        )?;
+
+        let file_code = if report.out_of_order > 0 {
+            EXIT_OUT_OF_ORDER
+        } else {
+            EXIT_DUPLICATES
+        };
+        code = code.max(file_code);
+    }
+
+    Ok(if read_error { 1 } else { code })
+}
+
+pub fn provenance(args: ProvenanceArgs) -> Result<ExitCode> {
+    let sidecar = args
+        .provenance_file
+        .unwrap_or_else(|| prov::sidecar_path(&args.output));
+
+    let line_number = if args.line_number {
+        args.line
+            .trim()
+            .parse::<u64>()
+            .ok()
+            .filter(|n| *n > 0)
+            .ok_or_else(|| anyhow!("Número de linha inválido: {}", args.line))?
+    } else {
+        match prov::find_line_number(&args.output, args.line.as_bytes())? {
+            Some(number) => number,
+            None => {
+                println!("Linha não encontrada em {}", args.output.display());
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+    };
+
+    let mut reader = ProvenanceReader::open(&sidecar)?;
+    match reader.sources_of(line_number)? {
+        Some(sources) => {
+            println!("Linha {}:", line_number);
+            for source in sources {
+                println!("  {}", source);
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+        None => {
+            println!("Linha {} não consta em {}", line_number, sidecar.display());
+            Ok(ExitCode::FAILURE)
+        }
+    }
+}
+
+// Um leitor que fecha o pipe cedo (`| head`) não é erro.
+pub fn print_plan(plan: &Plan) -> Result<()> {
+    match write_plan(plan, &mut io::stdout().lock()) {
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => result.context("Erro ao escrever o plano"),
+    }
+}
+
+fn write_plan(plan: &Plan, out: &mut impl Write) -> io::Result<()> {
+    writeln!(out, "Entradas ({}):", plan.files.len())?;
+    for file in &plan.files {
+        match file.size {
+            Some(size) => writeln!(out, "  {:>12}  {}", format_bytes(size), file.path),
+            None => writeln!(out, "  {:>12}  {}", "?", file.path),
+        }?;
+    }
+    if !plan.skipped_inputs.is_empty() {
+        writeln!(out)?;
+        writeln!(
+            out,
+            "Entradas duplicadas ignoradas ({}):",
+            plan.skipped_inputs.len()
+        )?;
+        for skipped in &plan.skipped_inputs {
+            writeln!(
+                out,
+                "  {} (duplicada de {})",
+                skipped.path, skipped.duplicate_of
+            )?;
+        }
+    }
+    writeln!(out)?;
+    writeln!(out, "Tamanho total: {}", format_bytes(plan.total_bytes))?;
+    if plan.unknown_sizes > 0 {
+        writeln!(
+            out,
+            "Entradas sem tamanho conhecido (fora das estimativas): {}",
+            plan.unknown_sizes
+        )?;
+    }
+    writeln!(
+        out,
+        "Tamanho médio de linha (amostra): {:.1} bytes",
+        plan.avg_line_len
+    )?;
+    writeln!(out, "Linhas estimadas: {}", plan.estimated_lines)?;
+    writeln!(out, "Chunks estimados: {}", plan.estimated_chunks)?;
+    writeln!(out, "Rodadas de merge: {}", plan.estimated_merge_rounds)?;
+    writeln!(
+        out,
+        "Pico de disco temporário: até {}",
+        format_bytes(plan.estimated_peak_temp_bytes)
+    )?;
+    writeln!(
+        out,
+        "Memória por chunk: ~{}",
+        format_bytes(plan.estimated_chunk_ram_bytes)
+    )?;
+    out.flush()
+}
+
+pub fn watch(args: WatchArgs) -> Result<ExitCode> {
+    let (config, options) = args.into_config();
+    let control = RunControl::new();
+    install_signal_handlers(&control)?;
+    match ulp_merge::watch::watch(config, &options, &control) {
+        Ok(()) => Ok(ExitCode::SUCCESS),
+        Err(err) if err.downcast_ref::<Cancelled>().is_some() => {
+            eprintln!("Observação encerrada.");
+            Ok(ExitCode::from(EXIT_CANCELLED))
+        }
+        Err(err) => Err(err),
+    }
+}
+
+pub fn batch(args: BatchArgs) -> Result<ExitCode> {
+    let batch = args.load()?;
+    let control = RunControl::new();
+    install_signal_handlers(&control)?;
+    let summary = batch::run(&batch, &control);
+    print_batch_summary(&summary);
+    if let Some(path) = args.report.as_deref() {
+        write_json(path, &summary)?;
+    }
+
+    let code = if summary.count(JobStatus::Cancelled) > 0 || control.cancellation().is_cancelled() {
+        EXIT_CANCELLED
+    } else if summary.count(JobStatus::Failed) > 0 {
+        1
+    } else if summary.count(JobStatus::Partial) > 0 {
+        EXIT_PARTIAL
+    } else {
+        0
+    };
+    Ok(ExitCode::from(code))
+}
+
+fn print_batch_summary(summary: &BatchSummary) {
+    let width = summary
+        .jobs
+        .iter()
+        .map(|job| job.name.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max(3);
+    eprintln!();
+    eprintln!(
+        "{:<width$}  {:<13}  {:>14}  {:>9}",
+        "Job", "Status", "Linhas únicas", "Tempo"
+    );
+    for job in &summary.jobs {
+        let status = match job.status {
+            JobStatus::Ok => "ok",
+            JobStatus::Partial => "parcial",
+            JobStatus::Failed => "falhou",
+            JobStatus::Cancelled => "cancelado",
+            JobStatus::Skipped => "não executado",
+        };
+        let lines = job
+            .summary
+            .as_ref()
+            .map_or_else(|| "-".to_string(), |s| s.unique_lines_written.to_string());
+        eprintln!(
+            "{:<width$}  {:<13}  {:>14}  {:>8.1}s",
+            job.name, status, lines, job.elapsed_secs
+        );
+    }
+    eprintln!(
+        "{} job(s): {} ok, {} parcial(is), {} com falha, {} cancelado(s), {} não executado(s) em {:.1}s",
+        summary.jobs.len(),
+        summary.count(JobStatus::Ok),
+        summary.count(JobStatus::Partial),
+        summary.count(JobStatus::Failed),
+        summary.count(JobStatus::Cancelled),
+        summary.count(JobStatus::Skipped),
+        summary.elapsed_secs
+    );
+}
+
+// O primeiro SIGINT/SIGTERM pede o cancelamento e o pipeline encerra limpando os temporários;
+// um segundo sinal encerra o processo imediatamente. SIGUSR1 pausa e SIGUSR2 retoma.
+#[cfg(unix)]
+pub fn install_signal_handlers(control: &RunControl) -> Result<()> {
+    use signal_hook::consts::{SIGINT, SIGTERM, SIGUSR1, SIGUSR2};
+    use signal_hook::{flag, low_level};
+    use std::sync::atomic::Ordering;
+
+    let cancel = control.cancellation();
+    for signal in [SIGINT, SIGTERM] {
+        flag::register_conditional_shutdown(signal, i32::from(EXIT_CANCELLED), cancel.flag())
+            .and_then(|_| flag::register(signal, cancel.flag()))
+            .context("Não foi possível instalar o tratamento de sinais")?;
+    }
+
+    flag::register(SIGUSR1, control.pause_token().flag())
+        .context("Não foi possível instalar o tratamento de sinais")?;
+    let paused = control.pause_token().flag();
+    // SAFETY: o tratador só faz um store atômico, que é seguro dentro de um sinal.
+    unsafe { low_level::register(SIGUSR2, move || paused.store(false, Ordering::SeqCst)) }
+        .context("Não foi possível instalar o tratamento de sinais")?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn install_signal_handlers(_control: &RunControl) -> Result<()> {
+    Ok(())
+}
+
+pub fn write_report(path: &Path, summary: &RunSummary) -> Result<()> {
+    write_json(path, summary)
+}
+
+fn write_json(path: &Path, summary: &impl serde::Serialize) -> Result<()> {
+    let file = File::create(path)
+        .with_context(|| format!("Não foi possível criar arquivo de relatório {:?}", path))?;
+    let mut writer = BufWriter::new(file);
+    serde_json::to_writer_pretty(&mut writer, summary)
+        .context("Falha ao serializar relatório de execução")?;
+    writer
+        .write_all(b"\n")
+        .and_then(|_| writer.flush())
+        .with_context(|| format!("Falha ao gravar relatório {:?}", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn check_reports_every_file_and_exit_code() {
+        let dir = tempdir().unwrap();
+        let sorted = dir.path().join("ok.txt");
+        let dupes = dir.path().join("dup.txt");
+        let unsorted = dir.path().join("desordem.txt");
+        let missing = dir.path().join("nao-existe.txt");
+        std::fs::write(&sorted, "a\nb\n").unwrap();
+        std::fs::write(&dupes, "a\na\nb\n").unwrap();
+        std::fs::write(&unsorted, "b\na\n").unwrap();
+
+        let mut out = Vec::new();
+        let code = check_files(&[sorted.clone(), dupes.clone()], &mut out).unwrap();
+        assert_eq!(code, EXIT_DUPLICATES);
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.contains("ok.txt: OK (2 linhas)"), "{text}");
+        assert!(text.contains("dup.txt: FALHA na linha 2"), "{text}");
+
+        let mut out = Vec::new();
+        let files = [missing, unsorted, sorted];
+        assert_eq!(check_files(&files, &mut out).unwrap(), 1);
+        let text = String::from_utf8(out).unwrap();
+        assert_eq!(text.lines().count(), 3, "{text}");
+        assert!(text.contains("nao-existe.txt: ERRO"), "{text}");
+        assert!(text.contains("desordem.txt: FALHA"), "{text}");
+
+        let mut out = Vec::new();
+        assert_eq!(
+            check_files(&files[1..], &mut out).unwrap(),
+            EXIT_OUT_OF_ORDER
+        );
+    }
+}
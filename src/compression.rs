@@ -0,0 +1,277 @@
+use anyhow::{Context, Result};
+use std::cell::Cell;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+const COMPRESSED_SUFFIXES: [&str; 4] = ["gz", "zst", "xz", "bz2"];
+// Após "BZh" e o dígito do tamanho de bloco vem o magic do primeiro bloco (ou o de fim de
+// stream, num arquivo vazio).
+const BZIP2_BLOCK_MAGIC: [u8; 6] = [0x31, 0x41, 0x59, 0x26, 0x53, 0x59];
+const BZIP2_EOS_MAGIC: [u8; 6] = [0x17, 0x72, 0x45, 0x38, 0x50, 0x90];
+// Maior cabeçalho reconhecido (o do bzip2).
+const MAGIC_LEN: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    Plain,
+    Gzip,
+    Zstd,
+    Xz,
+    Bzip2,
+}
+
+impl Codec {
+    pub fn detect(magic: &[u8]) -> Self {
+        if magic.starts_with(&[0x1f, 0x8b]) {
+            Codec::Gzip
+        } else if magic.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Codec::Zstd
+        } else if magic.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Codec::Xz
+        } else if is_bzip2(magic) {
+            Codec::Bzip2
+        } else {
+            Codec::Plain
+        }
+    }
+}
+
+// "BZh" sozinho também é o começo de texto comum; o cabeçalho completo tem 10 bytes.
+fn is_bzip2(magic: &[u8]) -> bool {
+    magic.len() >= MAGIC_LEN
+        && magic.starts_with(b"BZh")
+        && (b'1'..=b'9').contains(&magic[3])
+        && (magic[4..10] == BZIP2_BLOCK_MAGIC || magic[4..10] == BZIP2_EOS_MAGIC)
+}
+
+pub fn strip_compressed_suffix(path: &Path) -> PathBuf {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some(e)
+            if COMPRESSED_SUFFIXES
+                .iter()
+                .any(|s| e.eq_ignore_ascii_case(s)) =>
+        {
+            path.with_extension("")
+        }
+        _ => path.to_path_buf(),
+    }
+}
+
+pub struct InputReader<'a> {
+    inner: Box<dyn BufRead + 'a>,
+    consumed: Rc<Cell<u64>>,
+    total: u64,
+}
+
+impl InputReader<'static> {
+    pub fn open(path: &Path) -> Result<Self> {
+        let file =
+            File::open(path).with_context(|| format!("Falha ao abrir arquivo {:?}", path))?;
+        let total = file.metadata().map(|m| m.len()).unwrap_or(0);
+        InputReader::from_reader(file, total)
+            .with_context(|| format!("Falha ao ler cabeçalho de {:?}", path))
+    }
+}
+
+impl<'a> InputReader<'a> {
+    pub fn from_reader<R: Read + 'a>(reader: R, total: u64) -> io::Result<Self> {
+        let consumed = Rc::new(Cell::new(0));
+        let raw = BufReader::new(CountingReader {
+            inner: reader,
+            consumed: Rc::clone(&consumed),
+        });
+
+        Ok(Self {
+            inner: decode(raw)?,
+            consumed,
+            total,
+        })
+    }
+
+    pub fn consumed(&self) -> u64 {
+        self.consumed.get()
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+}
+
+// Pipes e a entrada padrão podem entregar menos bytes que o magic na primeira leitura; o
+// cabeçalho é acumulado até MAGIC_LEN (ou o fim) antes de escolher o codec.
+pub fn decode<'a, R: BufRead + 'a>(mut raw: R) -> io::Result<Box<dyn BufRead + 'a>> {
+    let mut header = Vec::with_capacity(MAGIC_LEN);
+    while header.len() < MAGIC_LEN {
+        let buf = match raw.fill_buf() {
+            Ok(buf) => buf,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        if buf.is_empty() {
+            break;
+        }
+        let n = buf.len().min(MAGIC_LEN - header.len());
+        header.extend_from_slice(&buf[..n]);
+        raw.consume(n);
+    }
+    let codec = Codec::detect(&header);
+    let raw = io::Cursor::new(header).chain(raw);
+    Ok(match codec {
+        Codec::Plain => Box::new(raw),
+        Codec::Gzip => Box::new(BufReader::new(flate2::bufread::MultiGzDecoder::new(raw))),
+        Codec::Zstd => Box::new(BufReader::new(zstd::Decoder::with_buffer(raw)?)),
+        Codec::Xz => Box::new(BufReader::new(xz2::bufread::XzDecoder::new_multi_decoder(
+            raw,
+        ))),
+        Codec::Bzip2 => Box::new(BufReader::new(bzip2::bufread::MultiBzDecoder::new(raw))),
+    })
+}
+
+impl Read for InputReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl BufRead for InputReader<'_> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.inner.consume(amt)
+    }
+}
+
+struct CountingReader<R> {
+    inner: R,
+    consumed: Rc<Cell<u64>>,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.consumed.set(self.consumed.get() + n as u64);
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lines::read_next_line;
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    fn read_all_lines(path: &Path) -> Vec<Vec<u8>> {
+        let mut reader = InputReader::open(path).unwrap();
+        let mut lines = Vec::new();
+        while let Some(line) = read_next_line(&mut reader).unwrap() {
+            lines.push(line);
+        }
+        assert_eq!(reader.consumed(), reader.total());
+        lines
+    }
+
+    #[test]
+    fn decompresses_by_magic_bytes() {
+        let dir = tempdir().unwrap();
+        let data = b"b\na\n";
+        let expected = vec![b"b".to_vec(), b"a".to_vec()];
+
+        let gz = dir.path().join("data.txt.gz");
+        let mut enc = flate2::write::GzEncoder::new(
+            File::create(&gz).unwrap(),
+            flate2::Compression::default(),
+        );
+        enc.write_all(data).unwrap();
+        enc.finish().unwrap();
+
+        let zst = dir.path().join("data.txt.zst");
+        std::fs::write(&zst, zstd::encode_all(&data[..], 0).unwrap()).unwrap();
+
+        let xz = dir.path().join("data.txt.xz");
+        let mut enc = xz2::write::XzEncoder::new(File::create(&xz).unwrap(), 6);
+        enc.write_all(data).unwrap();
+        enc.finish().unwrap();
+
+        let bz = dir.path().join("data.txt.bz2");
+        let mut enc =
+            bzip2::write::BzEncoder::new(File::create(&bz).unwrap(), bzip2::Compression::default());
+        enc.write_all(data).unwrap();
+        enc.finish().unwrap();
+
+        // Extensão enganosa: o codec vem do conteúdo, não do nome.
+        let plain = dir.path().join("plain.txt.gz");
+        std::fs::write(&plain, data).unwrap();
+
+        for path in [gz, zst, xz, bz, plain] {
+            assert_eq!(read_all_lines(&path), expected, "{:?}", path);
+        }
+    }
+
+    // Entrega um byte por leitura, como um pipe lento.
+    struct ByteAtATime<'a>(&'a [u8]);
+
+    impl Read for ByteAtATime<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let Some((first, rest)) = self.0.split_first() else {
+                return Ok(0);
+            };
+            if buf.is_empty() {
+                return Ok(0);
+            }
+            buf[0] = *first;
+            self.0 = rest;
+            Ok(1)
+        }
+    }
+
+    #[test]
+    fn detects_codec_across_short_reads() {
+        let data = b"b\na\n";
+        let zst = zstd::encode_all(&data[..], 0).unwrap();
+        let mut enc = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+        enc.write_all(data).unwrap();
+        let bz = enc.finish().unwrap();
+
+        for (compressed, plain) in [(&zst[..], &data[..]), (&bz[..], &data[..]), (b"x", b"x")] {
+            let mut reader = InputReader::from_reader(ByteAtATime(compressed), 0).unwrap();
+            let mut out = Vec::new();
+            reader.read_to_end(&mut out).unwrap();
+            assert_eq!(out, plain);
+        }
+    }
+
+    #[test]
+    fn text_starting_with_bzh_is_plain() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("bzh.txt");
+        std::fs::write(&path, b"BZh9 linha comum\nBZh\n").unwrap();
+        assert_eq!(
+            read_all_lines(&path),
+            vec![b"BZh9 linha comum".to_vec(), b"BZh".to_vec()]
+        );
+
+        let empty = dir.path().join("vazio.bz2");
+        let enc =
+            bzip2::write::BzEncoder::new(File::create(&empty).unwrap(), bzip2::Compression::best());
+        enc.finish().unwrap();
+        assert_eq!(Codec::detect(&std::fs::read(&empty).unwrap()), Codec::Bzip2);
+        assert!(read_all_lines(&empty).is_empty());
+    }
+
+    #[test]
+    fn strips_known_compression_suffixes() {
+        assert_eq!(
+            strip_compressed_suffix(Path::new("dir/a.txt.gz")),
+            Path::new("dir/a.txt")
+        );
+        assert_eq!(
+            strip_compressed_suffix(Path::new("dir/a.txt")),
+            Path::new("dir/a.txt")
+        );
+    }
+}
@@ -1,18 +1,484 @@
-use std::path::PathBuf;
+use crate::output::AtomicOutput;
+use anyhow::{anyhow, Context, Result};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 
-#[derive(Debug, Clone)]
+pub const STDIO_PATH: &str = "-";
+pub const DEFAULT_MIN_FREE_BYTES: u64 = 256 * 1024 * 1024;
+pub const DEFAULT_CHUNK_LINES: usize = 1_000_000;
+const PROFILES_KEY: &str = "profiles";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OverwritePolicy {
+    #[default]
+    NoClobber,
+    Force,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OverlapGrouping {
+    File,
+    TopDir,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TempPlacement {
+    #[default]
+    RoundRobin,
+    FreeSpace,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeltaOutput {
+    pub previous: PathBuf,
+    pub added: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub removed: Option<PathBuf>,
+}
+
+// Também é o formato dos arquivos de configuração (TOML): cada campo é uma chave, e os ausentes
+// ficam com os mesmos padrões da CLI.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct Config {
     pub output: PathBuf,
     pub inputs: Vec<PathBuf>,
-    pub ext: String,
+    pub extensions: Vec<String>,
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    pub skip_hidden: bool,
+    pub ignore_files: bool,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "deserialize_opt_depth"
+    )]
+    pub max_depth: Option<usize>,
+    pub follow_symlinks: bool,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "deserialize_opt_size"
+    )]
+    pub min_size: Option<u64>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "deserialize_opt_size"
+    )]
+    pub max_size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modified_after: Option<TimeFilter>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modified_before: Option<TimeFilter>,
+    pub dedupe_content: bool,
+    pub scan_threads: usize,
+    pub stream_scan: bool,
+    pub keep_going: bool,
+    pub read_retries: u32,
     pub recursive: bool,
     pub chunk_lines: usize,
-    pub temp_dir: Option<PathBuf>,
+    pub temp_dirs: Vec<PathBuf>,
+    pub temp_placement: TempPlacement,
     pub quiet: bool,
+    pub overwrite: OverwritePolicy,
+    pub track_sources: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub overlap: Option<OverlapGrouping>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delta: Option<DeltaOutput>,
+    // Modo do `watch`, ativado por ele; não é uma chave do arquivo de configuração.
+    #[serde(skip)]
+    pub incremental: bool,
+    pub space_check: bool,
+    #[serde(deserialize_with = "deserialize_size")]
+    pub min_free_bytes: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            output: PathBuf::new(),
+            inputs: Vec::new(),
+            extensions: vec!["txt".into()],
+            include: Vec::new(),
+            exclude: Vec::new(),
+            skip_hidden: false,
+            ignore_files: true,
+            max_depth: None,
+            follow_symlinks: false,
+            min_size: None,
+            max_size: None,
+            modified_after: None,
+            modified_before: None,
+            dedupe_content: false,
+            scan_threads: 0,
+            stream_scan: false,
+            keep_going: false,
+            read_retries: 0,
+            recursive: false,
+            chunk_lines: DEFAULT_CHUNK_LINES,
+            temp_dirs: Vec::new(),
+            temp_placement: TempPlacement::RoundRobin,
+            quiet: false,
+            overwrite: OverwritePolicy::NoClobber,
+            track_sources: false,
+            overlap: None,
+            provenance: None,
+            delta: None,
+            incremental: false,
+            space_check: false,
+            min_free_bytes: DEFAULT_MIN_FREE_BYTES,
+        }
+    }
 }
 
 impl Config {
+    pub fn from_toml_file(path: &Path, profile: Option<&str>) -> Result<Self> {
+        let text = fs::read_to_string(path).with_context(|| {
+            format!("Não foi possível ler o arquivo de configuração {:?}", path)
+        })?;
+        Self::from_toml_str(&text, profile)
+            .with_context(|| format!("Arquivo de configuração {:?} inválido", path))
+    }
+
+    // Os valores de `[profiles.<nome>]` substituem os do nível principal quando o perfil é
+    // escolhido; sem perfil, a tabela `profiles` é ignorada.
+    pub fn from_toml_str(text: &str, profile: Option<&str>) -> Result<Self> {
+        let mut table: toml::Table = text.parse()?;
+        let profiles = match table.remove(PROFILES_KEY) {
+            None => toml::Table::new(),
+            Some(toml::Value::Table(profiles)) => profiles,
+            Some(_) => return Err(anyhow!("`{}` deve ser uma tabela", PROFILES_KEY)),
+        };
+        if let Some(name) = profile {
+            let Some(toml::Value::Table(values)) = profiles.get(name) else {
+                let names: Vec<&str> = profiles.keys().map(String::as_str).collect();
+                return Err(anyhow!(
+                    "Perfil {:?} não encontrado (disponíveis: {})",
+                    name,
+                    if names.is_empty() {
+                        "nenhum".to_string()
+                    } else {
+                        names.join(", ")
+                    }
+                ));
+            };
+            table.extend(values.clone());
+        }
+        Self::from_table(table)
+    }
+
+    pub(crate) fn from_table(table: toml::Table) -> Result<Self> {
+        Ok(toml::Value::Table(table).try_into()?)
+    }
+
+    pub fn to_toml(&self) -> Result<String> {
+        toml::to_string(self).context("Falha ao serializar a configuração")
+    }
+
+    pub fn save_toml(&self, path: &Path) -> Result<()> {
+        let mut out = AtomicOutput::create(path, OverwritePolicy::Force)?;
+        out.write_all(self.to_toml()?.as_bytes()).with_context(|| {
+            format!(
+                "Não foi possível gravar o arquivo de configuração {:?}",
+                path
+            )
+        })?;
+        out.commit()
+    }
+
     pub fn validated_chunk_lines(&self) -> usize {
         self.chunk_lines.max(1)
     }
+
+    pub fn tags_sources(&self) -> bool {
+        self.track_sources || self.overlap.is_some() || self.provenance.is_some()
+    }
+
+    // No modo incremental a saída existente é incorporada ao resultado, não sobrescrita.
+    pub fn output_overwrite(&self) -> OverwritePolicy {
+        if self.incremental {
+            OverwritePolicy::Force
+        } else {
+            self.overwrite
+        }
+    }
+
+    pub fn writes_to_stdout(&self) -> bool {
+        is_stdio(&self.output)
+    }
+}
+
+pub fn is_stdio(path: &Path) -> bool {
+    path.as_os_str() == STDIO_PATH
+}
+
+pub fn parse_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let digits = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, suffix) = value.split_at(digits);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("Tamanho inválido: {}", value))?;
+    let multiplier: u64 = match suffix.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        "T" | "TB" | "TIB" => 1 << 40,
+        _ => return Err(format!("Unidade de tamanho desconhecida: {}", suffix)),
+    };
+    number
+        .checked_mul(multiplier)
+        .ok_or_else(|| format!("Tamanho muito grande: {}", value))
+}
+
+// Aceita uma idade relativa ao momento atual (`90s`, `30m`, `12h`, `7d`, `2w`) ou uma data
+// `AAAA-MM-DD`, interpretada como meia-noite UTC.
+pub fn parse_time(value: &str) -> Result<SystemTime, String> {
+    let value = value.trim();
+    if let Some(date) = parse_date(value) {
+        return Ok(date);
+    }
+    let digits = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, suffix) = value.split_at(digits);
+    let number: u64 = number.parse().map_err(|_| {
+        format!(
+            "Idade ou data inválida: {} (use 7d, 12h ou AAAA-MM-DD)",
+            value
+        )
+    })?;
+    let unit: u64 = match suffix.trim().to_ascii_lowercase().as_str() {
+        "s" => 1,
+        "m" | "min" => 60,
+        "h" => 60 * 60,
+        "" | "d" => 24 * 60 * 60,
+        "w" | "sem" => 7 * 24 * 60 * 60,
+        _ => return Err(format!("Unidade de tempo desconhecida: {}", suffix)),
+    };
+    let age = number
+        .checked_mul(unit)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("Idade muito grande: {}", value))?;
+    SystemTime::now()
+        .checked_sub(age)
+        .ok_or_else(|| format!("Idade muito grande: {}", value))
+}
+
+// Profundidade 0 não listaria nem os arquivos do próprio diretório.
+pub fn parse_depth(value: &str) -> Result<usize, String> {
+    match value.trim().parse::<usize>() {
+        Ok(0) => Err("A profundidade mínima é 1 (só o primeiro nível)".to_string()),
+        Ok(depth) => Ok(depth),
+        Err(_) => Err(format!("Profundidade inválida: {}", value)),
+    }
+}
+
+// Instante de --newer-than/--older-than junto com a forma em que foi informado: ao gravar a
+// configuração, uma idade como `7d` continua relativa ao momento de cada execução.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimeFilter {
+    pub at: SystemTime,
+    spec: TimeSpec,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TimeSpec {
+    Epoch(u64),
+    Text(String),
+}
+
+impl TimeFilter {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        Ok(Self {
+            at: parse_time(value)?,
+            spec: TimeSpec::Text(value.trim().to_string()),
+        })
+    }
+
+    pub fn from_epoch(secs: u64) -> Option<Self> {
+        Some(Self {
+            at: SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(secs))?,
+            spec: TimeSpec::Epoch(secs),
+        })
+    }
+}
+
+impl From<SystemTime> for TimeFilter {
+    fn from(at: SystemTime) -> Self {
+        let secs = at
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        Self {
+            at,
+            spec: TimeSpec::Epoch(secs),
+        }
+    }
+}
+
+fn parse_date(value: &str) -> Option<SystemTime> {
+    let mut parts = value.splitn(3, '-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: i64 = parts.next()?.parse().ok()?;
+    let day: i64 = parts.next()?.parse().ok()?;
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let month_days = match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        1..=12 => 31,
+        _ => return None,
+    };
+    if !(1..=month_days).contains(&day) {
+        return None;
+    }
+    // Dias desde 1970-01-01 pelo calendário gregoriano proléptico (algoritmo "days from civil").
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+    let secs = u64::try_from(days.checked_mul(24 * 60 * 60)?).ok()?;
+    SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(secs))
+}
+
+// Nos arquivos de configuração, tamanhos aceitam bytes (inteiro) ou texto como na CLI ("256M"),
+// e instantes aceitam segundos desde 1970 ou idade/data como em --newer-than.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum NumberOrText {
+    Number(u64),
+    Text(String),
+}
+
+fn deserialize_size<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    match NumberOrText::deserialize(deserializer)? {
+        NumberOrText::Number(bytes) => Ok(bytes),
+        NumberOrText::Text(text) => parse_size(&text).map_err(de::Error::custom),
+    }
+}
+
+fn deserialize_opt_depth<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<usize>, D::Error> {
+    match usize::deserialize(deserializer)? {
+        0 => Err(de::Error::custom("max_depth deve ser pelo menos 1")),
+        depth => Ok(Some(depth)),
+    }
+}
+
+fn deserialize_opt_size<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<u64>, D::Error> {
+    deserialize_size(deserializer).map(Some)
+}
+
+impl Serialize for TimeFilter {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match &self.spec {
+            TimeSpec::Epoch(secs) => serializer.serialize_u64(*secs),
+            TimeSpec::Text(text) => serializer.serialize_str(text),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for TimeFilter {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match NumberOrText::deserialize(deserializer)? {
+            NumberOrText::Number(secs) => TimeFilter::from_epoch(secs)
+                .ok_or_else(|| de::Error::custom(format!("Instante inválido: {}", secs))),
+            NumberOrText::Text(text) => TimeFilter::parse(&text).map_err(de::Error::custom),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JOB: &str = r#"
+output = "saida.txt"
+inputs = ["dump"]
+recursive = true
+min_size = "1K"
+min_free_bytes = 4096
+space_check = true
+modified_after = 1700000000
+
+[profiles.rapido]
+chunk_lines = 50
+space_check = false
+"#;
+
+    #[test]
+    fn profile_overrides_top_level_values() {
+        let base = Config::from_toml_str(JOB, None).unwrap();
+        assert_eq!(base.output, PathBuf::from("saida.txt"));
+        assert_eq!(base.chunk_lines, DEFAULT_CHUNK_LINES);
+        assert_eq!(base.extensions, vec!["txt".to_string()]);
+        assert_eq!(base.min_size, Some(1024));
+        assert!(base.space_check);
+
+        let fast = Config::from_toml_str(JOB, Some("rapido")).unwrap();
+        assert_eq!(fast.chunk_lines, 50);
+        assert!(!fast.space_check);
+        assert!(fast.recursive);
+
+        let err = Config::from_toml_str(JOB, Some("lento")).unwrap_err();
+        assert!(err.to_string().contains("rapido"));
+    }
+
+    #[test]
+    fn rejects_unknown_keys_and_round_trips() {
+        assert!(Config::from_toml_str("chunk_linhas = 10", None).is_err());
+
+        let config = Config::from_toml_str(JOB, Some("rapido")).unwrap();
+        let again = Config::from_toml_str(&config.to_toml().unwrap(), None).unwrap();
+        assert_eq!(again.to_toml().unwrap(), config.to_toml().unwrap());
+        assert_eq!(again.modified_after, config.modified_after);
+        assert!(Config::from_toml_str("max_depth = 0", None).is_err());
+        assert!(Config::from_toml_str("incremental = true", None).is_err());
+
+        // Idades relativas são gravadas como foram escritas, não como o instante calculado.
+        let config = Config::from_toml_str("modified_after = \"7d\"", None).unwrap();
+        assert!(config
+            .to_toml()
+            .unwrap()
+            .contains("modified_after = \"7d\""));
+    }
+
+    #[test]
+    fn rejects_impossible_dates_and_zero_depth() {
+        let day = |date: &str| {
+            parse_time(date)
+                .unwrap()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_secs()
+                / 86_400
+        };
+        assert_eq!(day("2024-02-29"), 19_782);
+        assert_eq!(day("2000-02-29"), 11_016);
+        assert!(parse_time("2023-02-29").is_err());
+        assert!(parse_time("2100-02-29").is_err());
+        assert!(parse_time("2024-02-31").is_err());
+        assert!(parse_time("2024-04-31").is_err());
+        assert!(parse_time("2024-13-01").is_err());
+
+        assert_eq!(parse_depth("2"), Ok(2));
+        assert!(parse_depth("0").is_err());
+    }
 }
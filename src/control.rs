@@ -0,0 +1,187 @@
+use crate::progress::ProgressSink;
+use anyhow::Result;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const PAUSE_POLL: Duration = Duration::from_millis(200);
+
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    // Exposto para que tratadores de sinal possam marcar o cancelamento sem alocar nem travar.
+    pub fn flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.cancelled)
+    }
+
+    pub fn check(&self) -> Result<()> {
+        if self.is_cancelled() {
+            return Err(Cancelled.into());
+        }
+        Ok(())
+    }
+
+    // Espera `duration`, mas retorna com erro assim que o job for cancelado.
+    pub fn wait(&self, duration: Duration) -> Result<()> {
+        let deadline = Instant::now() + duration;
+        loop {
+            self.check()?;
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                return Ok(());
+            }
+            std::thread::sleep(left.min(PAUSE_POLL));
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PauseToken {
+    paused: Arc<AtomicBool>,
+}
+
+impl PauseToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::SeqCst);
+    }
+
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    pub fn flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.paused)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RunControl {
+    cancel: CancellationToken,
+    pause: PauseToken,
+}
+
+impl RunControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancellation(&self) -> &CancellationToken {
+        &self.cancel
+    }
+
+    pub fn pause_token(&self) -> &PauseToken {
+        &self.pause
+    }
+
+    // Ponto de verificação entre linhas e chunks: falha se o job foi cancelado e bloqueia
+    // enquanto estiver pausado, avisando o `ProgressSink` na entrada da pausa e na retomada.
+    // Um cancelamento durante a pausa encerra sem retomada.
+    pub fn checkpoint(&self, progress: &mut dyn ProgressSink) -> Result<()> {
+        self.cancel.check()?;
+        if !self.pause.is_paused() {
+            return Ok(());
+        }
+
+        progress.paused();
+        while self.pause.is_paused() {
+            if self.cancel.is_cancelled() {
+                break;
+            }
+            std::thread::sleep(PAUSE_POLL);
+        }
+        self.cancel.check()?;
+        progress.resumed();
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Processamento cancelado")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cancellation_is_shared_between_clones() {
+        let token = CancellationToken::new();
+        let worker = token.clone();
+        assert!(worker.check().is_ok());
+        token.cancel();
+        let err = worker.check().unwrap_err();
+        assert!(err.downcast_ref::<Cancelled>().is_some());
+    }
+
+    #[derive(Default)]
+    struct Events(Vec<&'static str>);
+
+    impl ProgressSink for Events {
+        fn paused(&mut self) {
+            self.0.push("paused");
+        }
+        fn resumed(&mut self) {
+            self.0.push("resumed");
+        }
+    }
+
+    #[test]
+    fn checkpoint_blocks_while_paused() {
+        let control = RunControl::new();
+        let mut events = Events::default();
+        control.checkpoint(&mut events).unwrap();
+        assert!(events.0.is_empty());
+
+        control.pause_token().pause();
+        let pause = control.pause_token().clone();
+        let resumer = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            pause.resume();
+        });
+        control.checkpoint(&mut events).unwrap();
+        resumer.join().unwrap();
+        assert_eq!(events.0, vec!["paused", "resumed"]);
+
+        // Cancelar durante a pausa libera o job com erro.
+        control.pause_token().pause();
+        let cancel = control.cancellation().clone();
+        let canceller = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            cancel.cancel();
+        });
+        assert!(control.checkpoint(&mut events).is_err());
+        canceller.join().unwrap();
+        assert_eq!(events.0, vec!["paused", "resumed", "paused"]);
+    }
+}
@@ -0,0 +1,200 @@
+use crate::control::CancellationToken;
+use crate::scanner::{file_identity, InputFile};
+use crate::summary::{DuplicateReason, SkippedInput};
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::Path;
+
+const HASH_BLOCK: usize = 1024 * 1024;
+
+// Remove entradas que apontam para o mesmo arquivo (cópia via hardlink, diretórios de entrada
+// sobrepostos) e, se pedido, arquivos com conteúdo idêntico. A primeira ocorrência na ordem
+// da varredura é mantida. Entrada padrão, pipes e membros de pacotes não são comparados.
+pub fn remove_duplicates(
+    files: Vec<InputFile>,
+    by_content: bool,
+    cancel: &CancellationToken,
+) -> Result<(Vec<InputFile>, Vec<SkippedInput>)> {
+    let mut seen = SeenFiles::default();
+    let mut skipped = Vec::new();
+    let mut kept: Vec<InputFile> = Vec::with_capacity(files.len());
+    for file in files {
+        match seen.admit(&file) {
+            Some(duplicate) => skipped.push(duplicate),
+            None => kept.push(file),
+        }
+    }
+
+    if by_content {
+        kept = remove_same_content(kept, &mut skipped, cancel)?;
+    }
+    Ok((kept, skipped))
+}
+
+// Só arquivos com o mesmo tamanho têm o conteúdo lido e comparado por hash.
+fn remove_same_content(
+    files: Vec<InputFile>,
+    skipped: &mut Vec<SkippedInput>,
+    cancel: &CancellationToken,
+) -> Result<Vec<InputFile>> {
+    let mut by_size: HashMap<u64, Vec<usize>> = HashMap::new();
+    for (idx, file) in files.iter().enumerate() {
+        if let InputFile::Path(path) = file {
+            if let Ok(meta) = fs::metadata(path) {
+                if meta.is_file() {
+                    by_size.entry(meta.len()).or_default().push(idx);
+                }
+            }
+        }
+    }
+
+    let mut duplicate_of: HashMap<usize, usize> = HashMap::new();
+    for candidates in by_size.values().filter(|group| group.len() > 1) {
+        let mut by_hash: HashMap<blake3::Hash, usize> = HashMap::new();
+        for &idx in candidates {
+            let InputFile::Path(path) = &files[idx] else {
+                continue;
+            };
+            let hash = hash_file(path, cancel)?;
+            match by_hash.get(&hash) {
+                Some(&original) => {
+                    duplicate_of.insert(idx, original);
+                }
+                None => {
+                    by_hash.insert(hash, idx);
+                }
+            }
+        }
+    }
+
+    let mut kept = Vec::with_capacity(files.len() - duplicate_of.len());
+    let mut replaced: HashMap<String, String> = HashMap::new();
+    for (idx, file) in files.iter().enumerate() {
+        match duplicate_of.get(&idx) {
+            Some(&original) => {
+                let entry = skipped_input(file, &files[original], DuplicateReason::SameContent);
+                replaced.insert(entry.path.clone(), entry.duplicate_of.clone());
+                skipped.push(entry);
+            }
+            None => kept.push(file.clone()),
+        }
+    }
+    // Um caminho já descartado por ser o mesmo arquivo passa a apontar para a entrada mantida.
+    for entry in skipped.iter_mut() {
+        if let Some(original) = replaced.get(&entry.duplicate_of) {
+            entry.duplicate_of = original.clone();
+        }
+    }
+    Ok(kept)
+}
+
+// Lê em blocos para que um cancelamento não espere o hash de um arquivo grande terminar.
+fn hash_file(path: &Path, cancel: &CancellationToken) -> Result<blake3::Hash> {
+    let mut file =
+        File::open(path).with_context(|| format!("Falha ao abrir arquivo {:?}", path))?;
+    let mut hasher = blake3::Hasher::new();
+    let mut buffer = vec![0u8; HASH_BLOCK];
+    loop {
+        cancel.check()?;
+        let read = match file.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => {
+                return Err(err).with_context(|| format!("Falha ao calcular hash de {:?}", path))
+            }
+        };
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher.finalize())
+}
+
+fn skipped_input(file: &InputFile, original: &InputFile, reason: DuplicateReason) -> SkippedInput {
+    SkippedInput {
+        path: file.display_path().display().to_string(),
+        duplicate_of: original.display_path().display().to_string(),
+        reason,
+    }
+}
+
+// Caminhos para um mesmo arquivo já vistos, identificados por dispositivo e inode.
+#[derive(Default)]
+pub struct SeenFiles {
+    seen: HashMap<(u64, u64), String>,
+}
+
+impl SeenFiles {
+    // Devolve a entrada descartada quando `file` já apareceu por outro caminho.
+    pub fn admit(&mut self, file: &InputFile) -> Option<SkippedInput> {
+        let InputFile::Path(path) = file else {
+            return None;
+        };
+        let meta = fs::metadata(path).ok().filter(|meta| meta.is_file())?;
+        let identity = file_identity(&meta)?;
+        let display = file.display_path().display().to_string();
+        match self.seen.get(&identity) {
+            Some(original) => Some(SkippedInput {
+                path: display,
+                duplicate_of: original.clone(),
+                reason: DuplicateReason::SameFile,
+            }),
+            None => {
+                self.seen.insert(identity, display);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use tempfile::tempdir;
+
+    #[test]
+    fn skips_same_file_and_optionally_same_content() {
+        let dir = tempdir().unwrap();
+        let original = dir.path().join("a.txt");
+        let copy = dir.path().join("b.txt");
+        let other = dir.path().join("c.txt");
+        fs::write(&original, "x\ny\n").unwrap();
+        fs::write(&copy, "x\ny\n").unwrap();
+        fs::write(&other, "x\nz\n").unwrap();
+        let alias: PathBuf = dir.path().join(".").join("a.txt");
+        let files = vec![
+            InputFile::Path(original.clone()),
+            InputFile::Path(alias.clone()),
+            InputFile::Path(copy.clone()),
+            InputFile::Path(other.clone()),
+            InputFile::Stdin,
+        ];
+
+        let (kept, skipped) =
+            remove_duplicates(files.clone(), false, &CancellationToken::new()).unwrap();
+        assert_eq!(kept.len(), 4);
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].path, alias.display().to_string());
+        assert_eq!(skipped[0].duplicate_of, original.display().to_string());
+        assert_eq!(skipped[0].reason, DuplicateReason::SameFile);
+
+        // Com a cópia primeiro, o caminho alternativo passa a apontar para ela.
+        let mut files = files;
+        files.swap(0, 2);
+        let (kept, skipped) = remove_duplicates(files, true, &CancellationToken::new()).unwrap();
+        assert_eq!(
+            kept,
+            vec![
+                InputFile::Path(copy.clone()),
+                InputFile::Path(other),
+                InputFile::Stdin
+            ]
+        );
+        assert_eq!(skipped[0].path, original.display().to_string());
+        assert_eq!(skipped[0].duplicate_of, copy.display().to_string());
+        assert_eq!(skipped[1].path, alias.display().to_string());
+        assert_eq!(skipped[1].reason, DuplicateReason::SameContent);
+    }
+}
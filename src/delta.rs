@@ -0,0 +1,169 @@
+use crate::compression::InputReader;
+use crate::config::{DeltaOutput, OverwritePolicy};
+use crate::lines::read_next_line;
+use crate::output::AtomicOutput;
+use crate::summary::DeltaStats;
+use anyhow::{anyhow, Context, Result};
+use std::io::Write;
+use std::path::PathBuf;
+
+// Percorre a saída anterior (ordenada) em paralelo com o merge final, como um merge de
+// duas vias: linhas só na nova saída vão para o delta, linhas só na anterior para os removidos.
+pub struct DeltaWriter {
+    previous_path: PathBuf,
+    previous: InputReader<'static>,
+    pending: Option<Vec<u8>>,
+    previous_line: u64,
+    added: AtomicOutput,
+    removed: Option<AtomicOutput>,
+    stats: DeltaStats,
+}
+
+impl DeltaWriter {
+    pub fn create(delta: &DeltaOutput, overwrite: OverwritePolicy) -> Result<Self> {
+        let previous = InputReader::open(&delta.previous)?;
+        let added = AtomicOutput::create(&delta.added, overwrite)?;
+        let removed = delta
+            .removed
+            .as_deref()
+            .map(|path| AtomicOutput::create(path, overwrite))
+            .transpose()?;
+
+        let mut writer = Self {
+            previous_path: delta.previous.clone(),
+            previous,
+            pending: None,
+            previous_line: 0,
+            added,
+            removed,
+            stats: DeltaStats::default(),
+        };
+        writer.advance(None)?;
+        Ok(writer)
+    }
+
+    pub fn observe(&mut self, line: &[u8]) -> Result<()> {
+        while let Some(previous) = self.pending.take() {
+            match previous.as_slice().cmp(line) {
+                std::cmp::Ordering::Less => {
+                    self.write_removed(&previous)?;
+                    self.advance(Some(&previous))?;
+                }
+                std::cmp::Ordering::Equal => {
+                    self.advance(Some(&previous))?;
+                    return Ok(());
+                }
+                std::cmp::Ordering::Greater => {
+                    self.pending = Some(previous);
+                    break;
+                }
+            }
+        }
+
+        self.added
+            .write_all(line)
+            .and_then(|_| self.added.write_all(b"\n"))
+            .context("Erro ao escrever arquivo de delta")?;
+        self.stats.added += 1;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<DeltaStats> {
+        while let Some(previous) = self.pending.take() {
+            self.write_removed(&previous)?;
+            self.advance(Some(&previous))?;
+        }
+        self.added.commit()?;
+        if let Some(removed) = self.removed {
+            removed.commit()?;
+        }
+        Ok(self.stats)
+    }
+
+    fn write_removed(&mut self, line: &[u8]) -> Result<()> {
+        self.stats.removed += 1;
+        if let Some(removed) = self.removed.as_mut() {
+            removed
+                .write_all(line)
+                .and_then(|_| removed.write_all(b"\n"))
+                .context("Erro ao escrever arquivo de linhas removidas")?;
+        }
+        Ok(())
+    }
+
+    fn advance(&mut self, last: Option<&[u8]>) -> Result<()> {
+        loop {
+            let next = read_next_line(&mut self.previous).with_context(|| {
+                format!(
+                    "Erro ao ler linha da saída anterior {:?}",
+                    self.previous_path
+                )
+            })?;
+            let Some(next) = next else {
+                return Ok(());
+            };
+            self.previous_line += 1;
+            match last.map(|last| last.cmp(next.as_slice())) {
+                Some(std::cmp::Ordering::Equal) => continue,
+                Some(std::cmp::Ordering::Greater) => {
+                    return Err(anyhow!(
+                        "Saída anterior {:?} não está ordenada (linha {})",
+                        self.previous_path,
+                        self.previous_line
+                    ))
+                }
+                _ => {
+                    self.pending = Some(next);
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn splits_added_and_removed_lines() {
+        let dir = tempdir().unwrap();
+        let previous = dir.path().join("previous.txt");
+        std::fs::write(&previous, b"a\nc\nc\nd\nf\n").unwrap();
+        let delta = DeltaOutput {
+            previous,
+            added: dir.path().join("added.txt"),
+            removed: Some(dir.path().join("removed.txt")),
+        };
+
+        let mut writer = DeltaWriter::create(&delta, OverwritePolicy::NoClobber).unwrap();
+        for line in [b"b", b"c", b"e"] {
+            writer.observe(line).unwrap();
+        }
+        let stats = writer.finish().unwrap();
+
+        assert_eq!(stats.added, 2);
+        assert_eq!(stats.removed, 3);
+        assert_eq!(std::fs::read_to_string(&delta.added).unwrap(), "b\ne\n");
+        assert_eq!(
+            std::fs::read_to_string(delta.removed.unwrap()).unwrap(),
+            "a\nd\nf\n"
+        );
+    }
+
+    #[test]
+    fn rejects_unsorted_previous_output() {
+        let dir = tempdir().unwrap();
+        let previous = dir.path().join("previous.txt");
+        std::fs::write(&previous, b"b\na\n").unwrap();
+        let delta = DeltaOutput {
+            previous,
+            added: dir.path().join("added.txt"),
+            removed: None,
+        };
+
+        let mut writer = DeltaWriter::create(&delta, OverwritePolicy::NoClobber).unwrap();
+        assert!(writer.observe(b"c").is_err());
+    }
+}
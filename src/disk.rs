@@ -0,0 +1,176 @@
+use anyhow::{anyhow, Context, Result};
+use std::cell::Cell;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+#[cfg(unix)]
+pub fn available_space(path: &Path) -> io::Result<u64> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: c_path é uma string C válida e stat aponta para memória própria.
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+pub fn available_space(_path: &Path) -> io::Result<u64> {
+    Ok(u64::MAX)
+}
+
+#[cfg(unix)]
+fn filesystem_id(path: &Path) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    std::fs::metadata(path).ok().map(|m| m.dev())
+}
+
+#[cfg(not(unix))]
+fn filesystem_id(_path: &Path) -> Option<u64> {
+    None
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+// Soma as necessidades de diretórios que estão no mesmo sistema de arquivos e compara com o
+// espaço livre de cada um, descontando a reserva.
+pub fn preflight(needs: &[(&Path, u64)], reserve: u64) -> Result<()> {
+    let mut grouped: Vec<(Option<u64>, &Path, u64)> = Vec::new();
+    for (dir, bytes) in needs {
+        let id = filesystem_id(dir);
+        match grouped
+            .iter_mut()
+            .find(|(other, _, _)| id.is_some() && *other == id)
+        {
+            Some(entry) => entry.2 += bytes,
+            None => grouped.push((id, dir, *bytes)),
+        }
+    }
+
+    for (_, dir, needed) in grouped {
+        let available = available_space(dir)
+            .with_context(|| format!("Não foi possível consultar o espaço livre em {:?}", dir))?;
+        if needed.saturating_add(reserve) > available {
+            return Err(anyhow!(
+                "Espaço insuficiente em {:?}: estimativa de {} necessários mais {} de reserva, {} livres. Use --temp-dir para apontar outro disco, ajuste --min-free ou desative a verificação com --no-space-check.",
+                dir,
+                format_bytes(needed),
+                format_bytes(reserve),
+                format_bytes(available)
+            ));
+        }
+    }
+    Ok(())
+}
+
+pub struct SpaceGuard {
+    temp_dirs: Vec<PathBuf>,
+    output_dir: Option<PathBuf>,
+    reserve: u64,
+    last_check: Cell<Option<Instant>>,
+}
+
+impl SpaceGuard {
+    pub fn new(temp_dirs: Vec<PathBuf>, output_dir: Option<PathBuf>, reserve: u64) -> Self {
+        Self {
+            temp_dirs,
+            output_dir,
+            reserve,
+            last_check: Cell::new(None),
+        }
+    }
+
+    // Consulta o disco no máximo uma vez por segundo; pode ser chamada a cada escrita. Um
+    // diretório de temporários abaixo da reserva é marcado em `full` (alinhado com os diretórios
+    // passados em `new`) e deixa de receber arquivos; só a falta de espaço no diretório da saída
+    // interrompe o processamento.
+    pub fn check(&self, full: &mut [bool]) -> Result<()> {
+        let now = Instant::now();
+        if let Some(last) = self.last_check.get() {
+            if now.duration_since(last) < CHECK_INTERVAL {
+                return Ok(());
+            }
+        }
+        self.last_check.set(Some(now));
+
+        if let Some(dir) = &self.output_dir {
+            if let Some(available) = self.below_reserve(dir) {
+                return Err(anyhow!(
+                    "Espaço livre em {:?} caiu para {} (reserva mínima de {}). Processamento interrompido antes de encher o disco.",
+                    dir,
+                    format_bytes(available),
+                    format_bytes(self.reserve)
+                ));
+            }
+        }
+        for (dir, full) in self.temp_dirs.iter().zip(full.iter_mut()) {
+            if !*full && self.below_reserve(dir).is_some() {
+                *full = true;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn reserve(&self) -> u64 {
+        self.reserve
+    }
+
+    fn below_reserve(&self, dir: &Path) -> Option<u64> {
+        available_space(dir)
+            .ok()
+            .filter(|available| *available < self.reserve)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn rejects_needs_beyond_free_space() {
+        let dir = tempdir().unwrap();
+        assert!(available_space(dir.path()).unwrap() > 0);
+        preflight(&[(dir.path(), 1)], 0).unwrap();
+        assert!(preflight(&[(dir.path(), u64::MAX / 2), (dir.path(), u64::MAX / 2)], 0).is_err());
+
+        let temp_dirs = vec![dir.path().to_path_buf(), std::env::temp_dir()];
+        let guard = SpaceGuard::new(temp_dirs.clone(), None, u64::MAX);
+        let mut full = [false, false];
+        guard.check(&mut full).unwrap();
+        assert_eq!(full, [true, true]);
+
+        let guard = SpaceGuard::new(temp_dirs, Some(dir.path().to_path_buf()), u64::MAX);
+        let mut full = [false, false];
+        assert!(guard.check(&mut full).is_err());
+        // Dentro do intervalo a consulta não se repete.
+        assert!(guard.check(&mut full).is_ok());
+    }
+
+    #[test]
+    fn formats_sizes_with_binary_units() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(3 * 1024 * 1024 * 1024), "3.0 GiB");
+    }
+}
@@ -1,47 +1,419 @@
+mod archive;
+pub mod batch;
+pub mod check;
 mod chunker;
+mod compression;
 pub mod config;
+pub mod control;
+mod dedupe;
+mod delta;
+pub mod disk;
 mod lines;
+pub mod manifest;
 mod merger;
+mod output;
+pub mod plan;
 pub mod progress;
+pub mod provenance;
 mod scanner;
+mod sources;
+pub mod summary;
 mod temp;
+pub mod watch;
 
-pub use config::Config;
+pub use config::{Config, OverlapGrouping, OverwritePolicy};
+pub use control::{CancellationToken, Cancelled, PauseToken, RunControl};
 pub use progress::{ProgressReporter, ProgressSink};
+pub use summary::RunSummary;
 
-use anyhow::Result;
-use std::path::PathBuf;
+use anyhow::{anyhow, Result};
+use scanner::InputFile;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
 
-pub fn run(config: Config) -> Result<()> {
-    let (files, temp_factory) = prepare(&config)?;
-    let mut progress = progress::ProgressReporter::new(!config.quiet, files.len());
-    execute_pipeline(&config, files, temp_factory, &mut progress)
+const SCAN_QUEUE_BATCHES: usize = 1024;
+// Entradas lidas na estimativa prévia de espaço; as demais contam só pelo tamanho em disco.
+const PREFLIGHT_SAMPLE_FILES: usize = 8;
+
+pub fn run(config: Config, control: &RunControl) -> Result<RunSummary> {
+    let prepared = prepare(&config, control)?;
+    let mut progress = progress::ProgressReporter::new(!config.quiet, prepared.files.len());
+    execute_pipeline(&config, prepared, &mut progress, control)
 }
 
-pub fn run_with_progress(config: Config, progress: &mut dyn ProgressSink) -> Result<()> {
-    let (files, temp_factory) = prepare(&config)?;
-    execute_pipeline(&config, files, temp_factory, progress)
+pub fn plan(config: &Config) -> Result<plan::Plan> {
+    let (files, skipped) = scan(config, &CancellationToken::new())?;
+    let mut plan = plan::build(config, &files)?;
+    plan.skipped_inputs = skipped;
+    Ok(plan)
 }
 
-fn prepare(config: &Config) -> Result<(Vec<PathBuf>, temp::TempFileFactory)> {
-    let files = scanner::collect_input_files(config)?;
-    let temp_factory = temp::TempFileFactory::new(config.temp_dir.as_deref(), &config.output)?;
-    Ok((files, temp_factory))
+pub fn run_with_progress(
+    config: Config,
+    progress: &mut dyn ProgressSink,
+    control: &RunControl,
+) -> Result<RunSummary> {
+    let prepared = prepare(&config, control)?;
+    execute_pipeline(&config, prepared, progress, control)
 }
 
-fn execute_pipeline(
+struct Prepared {
+    files: Vec<InputFile>,
+    skipped: Vec<summary::SkippedInput>,
+    temp_factory: temp::TempFileFactory,
+    scan_time: Duration,
+}
+
+fn scan(
+    config: &Config,
+    cancel: &CancellationToken,
+) -> Result<(Vec<InputFile>, Vec<summary::SkippedInput>)> {
+    let files = scanner::collect_input_files(config, cancel)?;
+    dedupe::remove_duplicates(files, config.dedupe_content, cancel)
+}
+
+fn prepare(config: &Config, control: &RunControl) -> Result<Prepared> {
+    let started = Instant::now();
+    check_outputs(config)?;
+    // Na varredura em streaming as entradas só são conhecidas durante a leitura.
+    let (files, skipped) = if config.stream_scan {
+        if config.dedupe_content {
+            return Err(anyhow!(
+                "--stream-scan não pode ser combinado com --dedupe-content"
+            ));
+        }
+        (Vec::new(), Vec::new())
+    } else {
+        scan(config, control.cancellation())?
+    };
+    prepare_files(config, files, skipped, started, control)
+}
+
+// Executa o pipeline sobre entradas já conhecidas, sem varredura (usado pelo modo watch).
+pub(crate) fn run_files(
+    config: &Config,
+    files: Vec<InputFile>,
+    progress: &mut dyn ProgressSink,
+    control: &RunControl,
+) -> Result<RunSummary> {
+    let started = Instant::now();
+    check_outputs(config)?;
+    let prepared = prepare_files(config, files, Vec::new(), started, control)?;
+    execute_pipeline(config, prepared, progress, control)
+}
+
+fn check_outputs(config: &Config) -> Result<()> {
+    output::check_destination(&config.output, config.output_overwrite())?;
+    if config.incremental {
+        if config.writes_to_stdout() {
+            return Err(anyhow!("O modo incremental precisa de um arquivo de saída"));
+        }
+        if config.tags_sources() {
+            return Err(anyhow!(
+                "O modo incremental não pode ser combinado com estatísticas por entrada, sobreposição ou proveniência"
+            ));
+        }
+    }
+    if let Some(sidecar) = config.provenance.as_deref() {
+        if config::is_stdio(sidecar)
+            || (config.writes_to_stdout() && sidecar == provenance::sidecar_path(&config.output))
+        {
+            return Err(anyhow!(
+                "Informe --provenance-file ao gravar a saída na saída padrão"
+            ));
+        }
+        output::check_destination(sidecar, config.overwrite)?;
+    }
+    if let Some(delta) = config.delta.as_ref() {
+        if !delta.previous.is_file() {
+            return Err(anyhow!(
+                "Saída anterior {:?} não encontrada",
+                delta.previous
+            ));
+        }
+        output::check_destination(&delta.added, config.overwrite)?;
+        if let Some(removed) = delta.removed.as_deref() {
+            output::check_destination(removed, config.overwrite)?;
+        }
+    }
+    Ok(())
+}
+
+fn prepare_files(
+    config: &Config,
+    files: Vec<InputFile>,
+    skipped: Vec<summary::SkippedInput>,
+    started: Instant,
+    control: &RunControl,
+) -> Result<Prepared> {
+    let mut temp_factory = temp::TempFileFactory::new(&config.temp_dirs, &config.output)?
+        .with_placement(config.temp_placement);
+    if config.space_check {
+        temp_factory = guard_disk_space(config, &files, temp_factory, control)?;
+    }
+    Ok(Prepared {
+        files,
+        skipped,
+        temp_factory,
+        scan_time: started.elapsed(),
+    })
+}
+
+fn guard_disk_space(
     config: &Config,
-    files: Vec<PathBuf>,
+    files: &[InputFile],
     temp_factory: temp::TempFileFactory,
+    control: &RunControl,
+) -> Result<temp::TempFileFactory> {
+    let temp_dirs = temp_factory.dirs().to_vec();
+    let output_dir = output::parent_dir(&config.output).to_path_buf();
+    let guard = disk::SpaceGuard::new(
+        temp_dirs.clone(),
+        (!config.writes_to_stdout()).then(|| output_dir.clone()),
+        config.min_free_bytes,
+    );
+    if config.stream_scan {
+        // Sem a lista de entradas não há estimativa; resta o monitoramento durante a execução.
+        return Ok(temp_factory.with_space_guard(guard));
+    }
+
+    let estimate = plan::estimate(
+        config,
+        files,
+        PREFLIGHT_SAMPLE_FILES,
+        control.cancellation(),
+    )?;
+    // Os temporários são distribuídos entre os diretórios; cada um recebe sua fração.
+    let share = estimate
+        .estimated_peak_temp_bytes
+        .div_ceil(temp_dirs.len() as u64);
+    let mut needs: Vec<(&Path, u64)> = temp_dirs.iter().map(|d| (d.as_path(), share)).collect();
+    if !config.writes_to_stdout() {
+        // Sem deduplicação, a saída tem no máximo o tamanho das entradas descomprimidas.
+        let mut output_bytes = (estimate.avg_line_len * estimate.estimated_lines as f64) as u64;
+        if config.incremental {
+            output_bytes += std::fs::metadata(&config.output).map_or(0, |m| m.len());
+        }
+        needs.push((output_dir.as_path(), output_bytes));
+    }
+
+    disk::preflight(&needs, config.min_free_bytes)?;
+    Ok(temp_factory.with_space_guard(guard))
+}
+
+// Varre as entradas numa thread própria e entrega os lotes ao chunker assim que aparecem.
+// Se a leitura falhar ou for cancelada, a varredura é interrompida.
+fn scan_and_chunk(
+    config: &Config,
+    chunk_builder: &chunker::ChunkBuilder,
+    progress: &mut dyn ProgressSink,
+    control: &RunControl,
+) -> Result<(chunker::Chunks, Vec<InputFile>, Vec<summary::SkippedInput>)> {
+    let cancel = control.cancellation();
+    let (sender, receiver) = mpsc::sync_channel::<Vec<InputFile>>(SCAN_QUEUE_BATCHES);
+    let stop = AtomicBool::new(false);
+    let mut seen = dedupe::SeenFiles::default();
+    let mut skipped = Vec::new();
+
+    let (built, scanned) = thread::scope(|scope| {
+        let scanner = scope.spawn(|| {
+            scanner::scan_inputs(
+                config,
+                &|| stop.load(Ordering::Relaxed) || cancel.is_cancelled(),
+                &move |batch| sender.send(batch).is_ok(),
+            )
+        });
+        let batches = receiver.into_iter().map(|mut batch| {
+            batch.retain(|file| match seen.admit(file) {
+                Some(duplicate) => {
+                    skipped.push(duplicate);
+                    false
+                }
+                None => true,
+            });
+            batch
+        });
+        let built = chunk_builder.build_streaming(batches, progress);
+        stop.store(true, Ordering::Relaxed);
+        let scanned = scanner
+            .join()
+            .unwrap_or_else(|_| Err(anyhow!("A varredura de entradas foi interrompida")));
+        (built, scanned)
+    });
+
+    let (chunks, files) = built?;
+    scanned?;
+    cancel.check()?;
+    if files.is_empty() {
+        return Err(scanner::no_inputs_error());
+    }
+    for input in &skipped {
+        progress.skip_input(Path::new(&input.path), Path::new(&input.duplicate_of));
+    }
+    Ok((chunks, files, skipped))
+}
+
+fn execute_pipeline(
+    config: &Config,
+    prepared: Prepared,
     progress: &mut dyn ProgressSink,
-) -> Result<()> {
-    let chunk_builder = chunker::ChunkBuilder::new(config.validated_chunk_lines(), &temp_factory);
-    let temp_files = chunk_builder.build(&files, progress)?;
-    if !temp_files.is_empty() {
-        progress.start_merge(temp_files.len());
+    control: &RunControl,
+) -> Result<RunSummary> {
+    let Prepared {
+        files,
+        skipped,
+        temp_factory,
+        scan_time,
+    } = prepared;
+
+    let chunk_started = Instant::now();
+    let chunk_builder = chunker::ChunkBuilder::new(
+        config.validated_chunk_lines(),
+        config.tags_sources(),
+        &temp_factory,
+    )
+    .with_control(control.clone())
+    .with_failure_policy(config.keep_going, config.read_retries);
+    let (chunks, files, skipped) = if config.stream_scan {
+        scan_and_chunk(config, &chunk_builder, progress, control)?
+    } else {
+        for input in &skipped {
+            progress.skip_input(Path::new(&input.path), Path::new(&input.duplicate_of));
+        }
+        (chunk_builder.build(&files, progress)?, files, skipped)
+    };
+    let chunk_time = chunk_started.elapsed();
+    let chunk_count = chunks.temp_files.len();
+    if chunk_count > 0 {
+        progress.start_merge(chunk_count);
     }
 
-    merger::merge_chunks(temp_files, &config.output, &temp_factory, progress)?;
+    let merge_started = Instant::now();
+    let names: Vec<String> = files
+        .iter()
+        .map(|f| f.display_path().display().to_string())
+        .collect();
+    let mut tracker = None;
+    if config.tags_sources() {
+        let mut created = sources::SourceTracker::new(files.len());
+        if let Some(grouping) = config.overlap {
+            created = created.with_overlap(sources::OverlapTracker::new(
+                &files,
+                &config.inputs,
+                grouping,
+            ));
+        }
+        if let Some(sidecar) = config.provenance.as_deref() {
+            created = created.with_provenance(provenance::ProvenanceWriter::create(
+                sidecar,
+                config.overwrite,
+                &names,
+            )?);
+        }
+        tracker = Some(created);
+    }
+    let mut delta = config
+        .delta
+        .as_ref()
+        .map(|delta| delta::DeltaWriter::create(delta, config.overwrite))
+        .transpose()?;
+    // A saída existente só é lida durante o merge final, antes da troca atômica pelo resultado.
+    let base = (config.incremental && config.output.is_file()).then_some(config.output.as_path());
+    let mut writer = output::AtomicOutput::create(&config.output, config.output_overwrite())?;
+    let merged = merger::merge_chunks(
+        chunks.temp_files,
+        base,
+        merger::MergeOutputs {
+            writer: &mut writer,
+            tracker: tracker.as_mut(),
+            delta: delta.as_mut(),
+        },
+        &temp_factory,
+        control,
+        progress,
+    )?;
+    let delta = delta.map(delta::DeltaWriter::finish).transpose()?;
+    // O sidecar é confirmado antes da saída: se ele falhar, a saída anterior fica intacta.
+    if let Some(sidecar) = tracker.as_mut().and_then(|t| t.take_provenance()) {
+        sidecar.commit()?;
+    }
+    writer.commit()?;
+    let merge_time = merge_started.elapsed();
     progress.finish(&config.output);
-    Ok(())
+
+    let overlap = tracker.as_mut().and_then(|t| t.take_overlap());
+    let source_stats = match tracker {
+        Some(tracker) if config.track_sources => {
+            tracker.into_stats(names, &chunks.lines_per_source)
+        }
+        _ => Vec::new(),
+    };
+
+    Ok(RunSummary {
+        files_processed: files.len() - chunks.failed_files,
+        lines_read: chunks.lines_read,
+        unique_lines_written: merged.lines_written,
+        duplicates_removed: (chunks.lines_read + merged.base_lines)
+            .saturating_sub(merged.lines_written),
+        bytes_in: chunks.bytes_in,
+        bytes_out: merged.bytes_written,
+        chunks: chunk_count,
+        merge_rounds: merged.rounds,
+        timings: summary::PhaseTimings::new(scan_time, chunk_time, merge_time),
+        peak_temp_bytes: temp_factory.peak_usage(),
+        line_length_histogram: merged.histogram,
+        sources: source_stats,
+        overlap,
+        delta,
+        incremental: config.incremental.then(|| summary::IncrementalStats {
+            previous_lines: merged.base_lines,
+            added: merged.lines_written.saturating_sub(merged.base_lines),
+        }),
+        skipped_inputs: skipped,
+        failed_inputs: chunks.failures,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    #[cfg(unix)]
+    #[test]
+    fn space_check_does_not_consume_pipes() {
+        let dir = tempdir().unwrap();
+        let fifo = dir.path().join("pipe");
+        let status = std::process::Command::new("mkfifo")
+            .arg(&fifo)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let writer = {
+            let fifo = fifo.clone();
+            thread::spawn(move || {
+                let mut pipe = std::io::BufWriter::new(std::fs::File::create(fifo).unwrap());
+                for n in 0..30_000 {
+                    writeln!(pipe, "{:05}", n).unwrap();
+                }
+            })
+        };
+
+        let config = Config {
+            output: dir.path().join("out.txt"),
+            inputs: vec![fifo],
+            quiet: true,
+            space_check: true,
+            min_free_bytes: 0,
+            ..Config::default()
+        };
+        let summary = run(config, &RunControl::new()).unwrap();
+        writer.join().unwrap();
+        assert_eq!(summary.lines_read, 30_000);
+        assert_eq!(summary.unique_lines_written, 30_000);
+    }
 }
@@ -1,4 +1,6 @@
-use std::io::BufRead;
+use std::io::{self, BufRead, Write};
+
+pub type SourceId = u32;
 
 pub fn read_next_line<R: BufRead>(reader: &mut R) -> std::io::Result<Option<Vec<u8>>> {
     let mut buf = Vec::new();
@@ -22,3 +24,81 @@ pub fn trim_line_break(line: &mut Vec<u8>) {
         line.pop();
     }
 }
+
+// Sem rastreamento de origem os registros são só a linha, e a origem lida é sempre 0.
+pub fn read_next_record<R: BufRead>(
+    reader: &mut R,
+    tagged: bool,
+) -> io::Result<Option<(Vec<u8>, SourceId)>> {
+    let Some(mut buf) = read_next_line(reader)? else {
+        return Ok(None);
+    };
+    if !tagged {
+        return Ok(Some((buf, 0)));
+    }
+
+    // A origem fica após o último TAB: a linha original pode conter TABs, o sufixo não.
+    let source = buf
+        .iter()
+        .rposition(|b| *b == b'\t')
+        .and_then(|sep| {
+            let id = std::str::from_utf8(&buf[sep + 1..]).ok()?.parse().ok()?;
+            buf.truncate(sep);
+            Some(id)
+        })
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Registro temporário sem identificador de origem",
+            )
+        })?;
+    Ok(Some((buf, source)))
+}
+
+pub fn write_record<W: Write>(
+    writer: &mut W,
+    line: &[u8],
+    source: Option<SourceId>,
+) -> io::Result<()> {
+    writer.write_all(line)?;
+    match source {
+        Some(source) => writeln!(writer, "\t{}", source),
+        None => writer.write_all(b"\n"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_round_trip_lines_with_tabs() {
+        let mut buf = Vec::new();
+        write_record(&mut buf, b"a\tb", Some(7)).unwrap();
+        write_record(&mut buf, b"", Some(0)).unwrap();
+
+        let mut reader = &buf[..];
+        assert_eq!(
+            read_next_record(&mut reader, true).unwrap(),
+            Some((b"a\tb".to_vec(), 7))
+        );
+        assert_eq!(
+            read_next_record(&mut reader, true).unwrap(),
+            Some((Vec::new(), 0))
+        );
+        assert_eq!(read_next_record(&mut reader, true).unwrap(), None);
+    }
+
+    #[test]
+    fn untagged_records_are_plain_lines() {
+        let mut buf = Vec::new();
+        write_record(&mut buf, b"a\tb", None).unwrap();
+        assert_eq!(buf, b"a\tb\n");
+
+        let mut reader = &buf[..];
+        assert_eq!(
+            read_next_record(&mut reader, false).unwrap(),
+            Some((b"a\tb".to_vec(), 0))
+        );
+    }
+}
@@ -1,10 +1,63 @@
 mod cli;
+mod commands;
 
 use anyhow::Result;
-use clap::Parser;
+use clap::{CommandFactory, FromArgMatches};
+use std::process::ExitCode;
 
-fn main() -> Result<()> {
-    let cli = cli::Cli::parse();
-    let config = cli.into_config();
-    ulp_merge::run(config)
+fn main() -> Result<ExitCode> {
+    let matches = cli::Cli::command().get_matches();
+    let mut cli = cli::Cli::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+    match cli.command.take() {
+        Some(cli::Command::Check(args)) => commands::check(args),
+        Some(cli::Command::Provenance(args)) => commands::provenance(args),
+        Some(cli::Command::Watch(args)) => commands::watch(*args),
+        Some(cli::Command::Batch(args)) => commands::batch(args),
+        None => {
+            if cli.print_config {
+                print!("{}", cli.into_config(&matches)?.to_toml()?);
+                return Ok(ExitCode::SUCCESS);
+            }
+            if cli.dry_run {
+                let plan = ulp_merge::plan(&cli.into_config(&matches)?)?;
+                commands::print_plan(&plan)?;
+                return Ok(ExitCode::SUCCESS);
+            }
+            let report = cli.report.take();
+            let source_stats = cli.source_stats.clone();
+            let overlap = cli.overlap.clone();
+            let failure_report = cli.failure_report.take();
+            let config = cli.into_config(&matches)?;
+            let control = ulp_merge::RunControl::new();
+            commands::install_signal_handlers(&control)?;
+            let summary = match ulp_merge::run(config, &control) {
+                Ok(summary) => summary,
+                Err(err) if err.downcast_ref::<ulp_merge::Cancelled>().is_some() => {
+                    eprintln!("Processamento cancelado. Arquivos temporários removidos.");
+                    return Ok(ExitCode::from(commands::EXIT_CANCELLED));
+                }
+                Err(err) => return Err(err),
+            };
+            if let Some(path) = report {
+                commands::write_report(&path, &summary)?;
+            }
+            if let Some(path) = source_stats {
+                ulp_merge::summary::write_source_stats(&path, &summary.sources)?;
+            }
+            if let (Some(path), Some(report)) = (overlap, summary.overlap.as_ref()) {
+                ulp_merge::summary::write_overlap(&path, report)?;
+            }
+            if let Some(path) = failure_report {
+                ulp_merge::summary::write_failures(&path, &summary.failed_inputs)?;
+            }
+            if !summary.failed_inputs.is_empty() {
+                eprintln!(
+                    "Saída gerada sem o restante de {} entrada(s) que falharam na leitura (linhas lidas antes da falha foram mantidas).",
+                    summary.failed_inputs.len()
+                );
+                return Ok(ExitCode::from(commands::EXIT_PARTIAL));
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+    }
 }
@@ -0,0 +1,82 @@
+use crate::config::is_stdio;
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+// Lê uma lista de entradas (`@lista.txt` ou `--inputs-from`), uma por linha ou separadas por
+// NUL (`find -print0`). Linhas vazias são ignoradas; os caminhos são usados como estão,
+// relativos ao diretório atual.
+pub fn read_input_list(path: &Path, null_separated: bool) -> Result<Vec<PathBuf>> {
+    if is_stdio(path) {
+        let stdin = io::stdin();
+        return parse_input_list(stdin.lock(), null_separated)
+            .context("Falha ao ler a lista de entradas da entrada padrão");
+    }
+    let file = File::open(path)
+        .with_context(|| format!("Não foi possível abrir a lista de entradas {:?}", path))?;
+    parse_input_list(BufReader::new(file), null_separated)
+        .with_context(|| format!("Falha ao ler a lista de entradas {:?}", path))
+}
+
+fn parse_input_list<R: BufRead>(mut reader: R, null_separated: bool) -> io::Result<Vec<PathBuf>> {
+    let separator = if null_separated { b'\0' } else { b'\n' };
+    let mut paths = Vec::new();
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        if reader.read_until(separator, &mut buf)? == 0 {
+            break;
+        }
+        if buf.last() == Some(&separator) {
+            buf.pop();
+        }
+        if !null_separated && buf.last() == Some(&b'\r') {
+            buf.pop();
+        }
+        if !buf.is_empty() {
+            paths.push(path_from_bytes(&buf));
+        }
+    }
+    Ok(paths)
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn reads_line_and_nul_separated_lists() {
+        let parsed = parse_input_list(&b"a.txt\r\n\ndir/b.txt\nsem quebra"[..], false).unwrap();
+        assert_eq!(
+            parsed,
+            vec![
+                PathBuf::from("a.txt"),
+                PathBuf::from("dir/b.txt"),
+                PathBuf::from("sem quebra")
+            ]
+        );
+
+        let dir = tempdir().unwrap();
+        let list = dir.path().join("lista");
+        std::fs::write(&list, b"com\nquebra.txt\0outro.txt\0").unwrap();
+        assert_eq!(
+            read_input_list(&list, true).unwrap(),
+            vec![PathBuf::from("com\nquebra.txt"), PathBuf::from("outro.txt")]
+        );
+        assert!(read_input_list(&dir.path().join("ausente"), false).is_err());
+    }
+}
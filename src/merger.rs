@@ -1,28 +1,57 @@
-use crate::lines::read_next_line;
+use crate::compression::InputReader;
+use crate::control::RunControl;
+use crate::delta::DeltaWriter;
+use crate::lines::{read_next_line, read_next_record, write_record, SourceId};
+use crate::output::AtomicOutput;
 use crate::progress::ProgressSink;
+use crate::sources::SourceTracker;
+use crate::summary::LineLengthHistogram;
 use crate::temp::TempFileFactory;
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 use std::cmp::Reverse;
 use std::collections::BinaryHeap;
 use std::fs::File;
-use std::io::{BufReader, BufWriter, Write};
-use std::path::Path;
+use std::io::{BufReader, Write};
+use std::path::{Path, PathBuf};
 use tempfile::NamedTempFile;
 
-const MAX_OPEN_MERGE_FILES: usize = 64;
+pub(crate) const MAX_OPEN_MERGE_FILES: usize = 64;
+const SPACE_CHECK_LINES: u64 = 4096;
 
+#[derive(Debug, Default)]
+pub struct MergeStats {
+    pub rounds: usize,
+    pub lines_written: u64,
+    pub bytes_written: u64,
+    pub histogram: LineLengthHistogram,
+    pub base_lines: u64,
+}
+
+// Destinos do merge final: a saída e, opcionalmente, o rastreamento de origens e o delta.
+pub struct MergeOutputs<'a> {
+    pub writer: &'a mut AtomicOutput,
+    pub tracker: Option<&'a mut SourceTracker>,
+    pub delta: Option<&'a mut DeltaWriter>,
+}
+
+// `base`, quando informado, é uma saída anterior (já ordenada) incorporada ao merge final.
+// A saída não é confirmada aqui: quem chama grava os arquivos auxiliares antes dela.
 pub fn merge_chunks(
     mut temp_files: Vec<NamedTempFile>,
-    output: &Path,
+    base: Option<&Path>,
+    outputs: MergeOutputs<'_>,
     temp_factory: &TempFileFactory,
+    control: &RunControl,
     progress: &mut dyn ProgressSink,
-) -> Result<()> {
-    if temp_files.is_empty() {
-        File::create(output)
-            .with_context(|| format!("Não foi possível criar arquivo de saída {:?}", output))?;
-        return Ok(());
+) -> Result<MergeStats> {
+    if temp_files.is_empty() && base.is_none() {
+        return Ok(MergeStats::default());
     }
 
+    let mut rounds = 0;
+    // As origens só são gravadas nos temporários quando há quem as consuma.
+    let tagged = outputs.tracker.is_some();
+
     while temp_files.len() > MAX_OPEN_MERGE_FILES {
         let mut next_round: Vec<NamedTempFile> = Vec::new();
         let mut group: Vec<NamedTempFile> = Vec::new();
@@ -30,7 +59,7 @@ pub fn merge_chunks(
         for temp_file in temp_files.into_iter() {
             group.push(temp_file);
             if group.len() == MAX_OPEN_MERGE_FILES {
-                let merged = merge_group_into_temp(group, temp_factory)?;
+                let merged = merge_group_into_temp(group, tagged, temp_factory, control, progress)?;
                 next_round.push(merged);
                 group = Vec::new();
             }
@@ -40,95 +69,237 @@ pub fn merge_chunks(
             if group.len() == 1 {
                 next_round.push(group.pop().unwrap());
             } else {
-                let merged = merge_group_into_temp(group, temp_factory)?;
+                let merged = merge_group_into_temp(group, tagged, temp_factory, control, progress)?;
                 next_round.push(merged);
             }
         }
 
+        rounds += 1;
         progress.merge_round(next_round.len());
         temp_files = next_round;
     }
 
-    let out_file = File::create(output)
-        .with_context(|| format!("Não foi possível criar arquivo de saída {:?}", output))?;
-    let mut writer = BufWriter::new(out_file);
-    merge_into_writer(&temp_files, &mut writer)?;
-    writer
-        .flush()
-        .context("Falha ao finalizar escrita do arquivo de saída")?;
-    Ok(())
+    let mut records = RecordMerge::open(&temp_files, tagged)?;
+    if let Some(base) = base {
+        records.base = Some(BaseRun::open(base)?);
+    }
+    let mut stats = merge_into_output(records, outputs, temp_factory, control, progress)?;
+    stats.rounds = rounds + 1;
+    Ok(stats)
 }
 
 fn merge_group_into_temp(
     group: Vec<NamedTempFile>,
+    tagged: bool,
     temp_factory: &TempFileFactory,
+    control: &RunControl,
+    progress: &mut dyn ProgressSink,
 ) -> Result<NamedTempFile> {
-    let mut tmp = temp_factory
-        .create()
-        .context("Não foi possível criar arquivo temporário para merge")?;
-    {
-        let mut writer = BufWriter::new(&mut tmp);
-        merge_into_writer(&group, &mut writer)?;
+    let reading: Vec<&Path> = group.iter().filter_map(|t| t.path().parent()).collect();
+    let tmp = temp_factory.write_temp(&reading, |writer| {
+        let mut records = RecordMerge::open(&group, tagged)?;
+        let mut last_written: Option<(Vec<u8>, SourceId)> = None;
+        let mut written = 0u64;
+        while let Some(record) = records.next_record()? {
+            control.checkpoint(progress)?;
+            if last_written.as_ref() == Some(&record) {
+                continue;
+            }
+            written += 1;
+            if written.is_multiple_of(SPACE_CHECK_LINES) {
+                temp_factory.ensure_space()?;
+            }
+            write_record(writer, &record.0, tagged.then_some(record.1))
+                .context("Erro ao escrever no destino de merge")?;
+            last_written = Some(record);
+        }
+        Ok(())
+    })?;
+
+    let released: u64 = group
+        .iter()
+        .map(|t| t.as_file().metadata().map(|m| m.len()).unwrap_or(0))
+        .sum();
+    drop(group);
+    temp_factory.track_released(released);
+    Ok(tmp)
+}
+
+fn merge_into_output(
+    mut records: RecordMerge,
+    outputs: MergeOutputs<'_>,
+    temp_factory: &TempFileFactory,
+    control: &RunControl,
+    progress: &mut dyn ProgressSink,
+) -> Result<MergeStats> {
+    let MergeOutputs {
+        writer,
+        mut tracker,
+        mut delta,
+    } = outputs;
+    let mut stats = MergeStats::default();
+    let mut current: Option<Vec<u8>> = None;
+    let mut current_sources: Vec<SourceId> = Vec::new();
+
+    while let Some((line, source)) = records.next_record()? {
+        control.checkpoint(progress)?;
+        if current.as_ref() == Some(&line) {
+            if current_sources.last() != Some(&source) {
+                current_sources.push(source);
+            }
+            continue;
+        }
+
+        if let Some(tracker) = tracker.as_deref_mut() {
+            if current.is_some() {
+                tracker.observe(&current_sources)?;
+            }
+        }
+
+        if let Some(delta) = delta.as_deref_mut() {
+            delta.observe(&line)?;
+        }
+
         writer
-            .flush()
-            .context("Erro ao finalizar escrita de arquivo temporário de merge")?;
+            .write_all(&line)
+            .context("Erro ao escrever no destino de merge")?;
+        writer
+            .write_all(b"\n")
+            .context("Erro ao escrever quebra de linha no destino de merge")?;
+        stats.lines_written += 1;
+        if stats.lines_written.is_multiple_of(SPACE_CHECK_LINES) {
+            temp_factory.ensure_space()?;
+        }
+        stats.bytes_written += line.len() as u64 + 1;
+        stats.histogram.record(line.len());
+        current = Some(line);
+        current_sources.clear();
+        current_sources.push(source);
     }
 
-    Ok(tmp)
+    if let (Some(tracker), Some(_)) = (tracker, current) {
+        tracker.observe(&current_sources)?;
+    }
+
+    stats.base_lines = records.base.map_or(0, |base| base.lines);
+    Ok(stats)
 }
 
-fn merge_into_writer<W: Write>(sources: &[NamedTempFile], writer: &mut W) -> Result<()> {
-    if sources.is_empty() {
-        return Ok(());
+struct RecordMerge {
+    readers: Vec<BufReader<File>>,
+    heap: BinaryHeap<Reverse<(Vec<u8>, SourceId, usize)>>,
+    base: Option<BaseRun>,
+    tagged: bool,
+}
+
+// Saída anterior lida como mais uma sequência ordenada do merge; as linhas não têm origem
+// (o modo incremental não rastreia origens), então todas usam o identificador 0.
+struct BaseRun {
+    path: PathBuf,
+    reader: InputReader<'static>,
+    pending: Option<Vec<u8>>,
+    lines: u64,
+}
+
+impl BaseRun {
+    fn open(path: &Path) -> Result<Self> {
+        let mut run = Self {
+            path: path.to_path_buf(),
+            reader: InputReader::open(path)?,
+            pending: None,
+            lines: 0,
+        };
+        run.pending = run.read_next()?;
+        Ok(run)
     }
 
-    let mut readers: Vec<BufReader<File>> = Vec::with_capacity(sources.len());
-    for tmp in sources {
-        let file = tmp
-            .reopen()
-            .context("Não foi possível reabrir arquivo temporário para leitura")?;
-        readers.push(BufReader::new(file));
+    fn take(&mut self) -> Result<Option<Vec<u8>>> {
+        let Some(line) = self.pending.take() else {
+            return Ok(None);
+        };
+        // `lines` conta as linhas lidas até `line`; a seguinte é a que quebra a ordem.
+        let line_number = self.lines;
+        self.pending = self.read_next()?;
+        if self.pending.as_ref().is_some_and(|next| *next < line) {
+            return Err(anyhow!(
+                "Saída existente {:?} não está ordenada (linha {})",
+                self.path,
+                line_number + 1
+            ));
+        }
+        Ok(Some(line))
     }
 
-    let mut heap: BinaryHeap<Reverse<(Vec<u8>, usize)>> = BinaryHeap::new();
-    for (idx, reader) in readers.iter_mut().enumerate() {
-        if let Some(line) = read_next_line(reader).context("Erro ao ler de arquivo temporário")? {
-            heap.push(Reverse((line, idx)));
+    fn read_next(&mut self) -> Result<Option<Vec<u8>>> {
+        let next = read_next_line(&mut self.reader)
+            .with_context(|| format!("Erro ao ler linha da saída existente {:?}", self.path))?;
+        if next.is_some() {
+            self.lines += 1;
         }
+        Ok(next)
     }
+}
 
-    let mut last_written: Option<Vec<u8>> = None;
-    while let Some(Reverse((line, idx))) = heap.pop() {
-        let should_write = match &last_written {
-            Some(last) => last != &line,
-            None => true,
-        };
+impl RecordMerge {
+    fn open(sources: &[NamedTempFile], tagged: bool) -> Result<Self> {
+        let mut readers: Vec<BufReader<File>> = Vec::with_capacity(sources.len());
+        for tmp in sources {
+            let file = tmp
+                .reopen()
+                .context("Não foi possível reabrir arquivo temporário para leitura")?;
+            readers.push(BufReader::new(file));
+        }
 
-        if should_write {
-            writer
-                .write_all(&line)
-                .context("Erro ao escrever no destino de merge")?;
-            writer
-                .write_all(b"\n")
-                .context("Erro ao escrever quebra de linha no destino de merge")?;
-            last_written = Some(line.clone());
+        let mut heap = BinaryHeap::with_capacity(readers.len());
+        for (idx, reader) in readers.iter_mut().enumerate() {
+            if let Some((line, source)) =
+                read_next_record(reader, tagged).context("Erro ao ler de arquivo temporário")?
+            {
+                heap.push(Reverse((line, source, idx)));
+            }
         }
 
-        if let Some(next_line) =
-            read_next_line(&mut readers[idx]).context("Erro ao ler de arquivo temporário")?
+        Ok(Self {
+            readers,
+            heap,
+            base: None,
+            tagged,
+        })
+    }
+
+    fn next_record(&mut self) -> Result<Option<(Vec<u8>, SourceId)>> {
+        if let Some(base) = self.base.as_mut() {
+            let base_first = match (base.pending.as_ref(), self.heap.peek()) {
+                (Some(line), Some(Reverse((top, _, _)))) => line <= top,
+                (Some(_), None) => true,
+                (None, _) => false,
+            };
+            if base_first {
+                return Ok(base.take()?.map(|line| (line, 0)));
+            }
+        }
+
+        let Some(Reverse((line, source, idx))) = self.heap.pop() else {
+            return Ok(None);
+        };
+
+        if let Some((next_line, next_source)) =
+            read_next_record(&mut self.readers[idx], self.tagged)
+                .context("Erro ao ler de arquivo temporário")?
         {
-            heap.push(Reverse((next_line, idx)));
+            self.heap.push(Reverse((next_line, next_source, idx)));
         }
-    }
 
-    Ok(())
+        Ok(Some((line, source)))
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::OverwritePolicy;
     use crate::progress::ProgressSink;
-    use std::io::Write;
+    use std::io::{BufWriter, Write};
     use tempfile::tempdir;
 
     struct NoopProgress;
@@ -138,7 +309,7 @@ mod tests {
     fn merges_and_deduplicates_all_chunks() {
         let dir = tempdir().unwrap();
         let output = dir.path().join("merged.txt");
-        let factory = TempFileFactory::new(Some(dir.path()), &output).unwrap();
+        let factory = TempFileFactory::new(&[dir.path().to_path_buf()], &output).unwrap();
 
         let mut tmp1 = factory.create().unwrap();
         {
@@ -155,8 +326,137 @@ mod tests {
         }
 
         let mut progress = NoopProgress;
-        merge_chunks(vec![tmp1, tmp2], &output, &factory, &mut progress).unwrap();
+        let mut writer = AtomicOutput::create(&output, OverwritePolicy::NoClobber).unwrap();
+        let stats = merge_chunks(
+            vec![tmp1, tmp2],
+            None,
+            MergeOutputs {
+                writer: &mut writer,
+                tracker: None,
+                delta: None,
+            },
+            &factory,
+            &RunControl::default(),
+            &mut progress,
+        )
+        .unwrap();
+        writer.commit().unwrap();
         let result = std::fs::read_to_string(&output).unwrap();
         assert_eq!(result, "a\nb\nc\n");
+        assert_eq!(stats.rounds, 1);
+        assert_eq!(stats.lines_written, 3);
+        assert_eq!(stats.bytes_written, 6);
+    }
+
+    #[test]
+    fn tracks_sources_of_tagged_records() {
+        let dir = tempdir().unwrap();
+        let output = dir.path().join("merged.txt");
+        let factory = TempFileFactory::new(&[dir.path().to_path_buf()], &output).unwrap();
+
+        let mut tmp1 = factory.create().unwrap();
+        {
+            let mut writer = BufWriter::new(&mut tmp1);
+            writer.write_all(b"a\t0\nc\t0\n").unwrap();
+            writer.flush().unwrap();
+        }
+
+        let mut tmp2 = factory.create().unwrap();
+        {
+            let mut writer = BufWriter::new(&mut tmp2);
+            writer.write_all(b"b\t1\nc\t1\n").unwrap();
+            writer.flush().unwrap();
+        }
+
+        let mut progress = NoopProgress;
+        let mut tracker = SourceTracker::new(2);
+        let mut writer = AtomicOutput::create(&output, OverwritePolicy::NoClobber).unwrap();
+        let stats = merge_chunks(
+            vec![tmp1, tmp2],
+            None,
+            MergeOutputs {
+                writer: &mut writer,
+                tracker: Some(&mut tracker),
+                delta: None,
+            },
+            &factory,
+            &RunControl::default(),
+            &mut progress,
+        )
+        .unwrap();
+        writer.commit().unwrap();
+        let result = std::fs::read_to_string(&output).unwrap();
+        assert_eq!(result, "a\nb\nc\n");
+        assert_eq!(stats.rounds, 1);
+        assert_eq!(stats.lines_written, 3);
+        assert_eq!(stats.bytes_written, 6);
+
+        let sources = tracker.into_stats(vec!["1".into(), "2".into()], &[2, 2]);
+        assert_eq!(sources[0].unique_lines, 2);
+        assert_eq!(sources[0].exclusive_lines, 1);
+        assert_eq!(sources[1].unique_lines, 2);
+        assert_eq!(sources[1].exclusive_lines, 1);
+    }
+
+    #[test]
+    fn folds_existing_output_into_final_merge() {
+        let dir = tempdir().unwrap();
+        let output = dir.path().join("merged.txt");
+        std::fs::write(&output, b"a\nc\ne\n").unwrap();
+        let factory = TempFileFactory::new(&[dir.path().to_path_buf()], &output).unwrap();
+        let mut tmp = factory.create().unwrap();
+        tmp.write_all(b"b\nc\nf\n").unwrap();
+
+        let mut writer = AtomicOutput::create(&output, OverwritePolicy::Force).unwrap();
+        let stats = merge_chunks(
+            vec![tmp],
+            Some(&output),
+            MergeOutputs {
+                writer: &mut writer,
+                tracker: None,
+                delta: None,
+            },
+            &factory,
+            &RunControl::default(),
+            &mut NoopProgress,
+        )
+        .unwrap();
+        writer.commit().unwrap();
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "a\nb\nc\ne\nf\n");
+        assert_eq!(stats.base_lines, 3);
+        assert_eq!(stats.lines_written, 5);
+
+        std::fs::write(&output, b"b\na\n").unwrap();
+        let result = merge_chunks(
+            Vec::new(),
+            Some(&output),
+            MergeOutputs {
+                writer: &mut AtomicOutput::create(&output, OverwritePolicy::Force).unwrap(),
+                tracker: None,
+                delta: None,
+            },
+            &factory,
+            &RunControl::default(),
+            &mut NoopProgress,
+        );
+        let err = format!("{:#}", result.unwrap_err());
+        assert!(err.contains("(linha 2)"), "{err}");
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "b\na\n");
+
+        std::fs::write(&output, b"a\nb\nd\nc\n").unwrap();
+        let result = merge_chunks(
+            Vec::new(),
+            Some(&output),
+            MergeOutputs {
+                writer: &mut AtomicOutput::create(&output, OverwritePolicy::Force).unwrap(),
+                tracker: None,
+                delta: None,
+            },
+            &factory,
+            &RunControl::default(),
+            &mut NoopProgress,
+        );
+        let err = format!("{:#}", result.unwrap_err());
+        assert!(err.contains("(linha 4)"), "{err}");
     }
 }
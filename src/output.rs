@@ -0,0 +1,221 @@
+use crate::config::{is_stdio, OverwritePolicy};
+use anyhow::{anyhow, Context, Result};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, StdoutLock, Write};
+use std::path::{Path, PathBuf};
+use tempfile::{Builder, NamedTempFile};
+
+pub struct AtomicOutput {
+    destination: PathBuf,
+    policy: OverwritePolicy,
+    target: Target,
+}
+
+enum Target {
+    Stdout(BufWriter<StdoutLock<'static>>),
+    File(BufWriter<NamedTempFile>),
+}
+
+pub fn check_destination(destination: &Path, policy: OverwritePolicy) -> Result<()> {
+    if is_stdio(destination) || policy == OverwritePolicy::Force {
+        return Ok(());
+    }
+    if destination.exists() {
+        return Err(already_exists(destination));
+    }
+    Ok(())
+}
+
+impl AtomicOutput {
+    pub fn create(destination: &Path, policy: OverwritePolicy) -> Result<Self> {
+        let target = if is_stdio(destination) {
+            Target::Stdout(BufWriter::new(io::stdout().lock()))
+        } else {
+            check_destination(destination, policy)?;
+            let tmp = output_builder()
+                .tempfile_in(parent_dir(destination))
+                .with_context(|| {
+                    format!(
+                        "Não foi possível criar arquivo temporário ao lado da saída {:?}",
+                        destination
+                    )
+                })?;
+            Target::File(BufWriter::new(tmp))
+        };
+
+        Ok(Self {
+            destination: destination.to_path_buf(),
+            policy,
+            target,
+        })
+    }
+
+    pub fn commit(self) -> Result<()> {
+        match self.target {
+            Target::Stdout(mut writer) => writer
+                .flush()
+                .context("Falha ao finalizar escrita na saída padrão"),
+            Target::File(writer) => {
+                let tmp = writer
+                    .into_inner()
+                    .map_err(|err| err.into_error())
+                    .context("Falha ao finalizar escrita do arquivo de saída")?;
+                // Ao substituir um arquivo, o resultado herda as permissões dele.
+                if let Ok(meta) = fs::metadata(&self.destination) {
+                    tmp.as_file()
+                        .set_permissions(meta.permissions())
+                        .with_context(|| {
+                            format!("Falha ao copiar as permissões de {:?}", self.destination)
+                        })?;
+                }
+                tmp.as_file()
+                    .sync_all()
+                    .context("Falha ao sincronizar arquivo de saída com o disco")?;
+
+                let persisted = match self.policy {
+                    OverwritePolicy::Force => tmp.persist(&self.destination),
+                    OverwritePolicy::NoClobber => tmp.persist_noclobber(&self.destination),
+                };
+                if let Err(err) = persisted {
+                    if err.error.kind() == io::ErrorKind::AlreadyExists {
+                        return Err(already_exists(&self.destination));
+                    }
+                    return Err(err.error).with_context(|| {
+                        format!(
+                            "Não foi possível mover o resultado para {:?}",
+                            self.destination
+                        )
+                    });
+                }
+
+                sync_dir(parent_dir(&self.destination));
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Write for AtomicOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.target {
+            Target::Stdout(writer) => writer.write(buf),
+            Target::File(writer) => writer.write(buf),
+        }
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        match &mut self.target {
+            Target::Stdout(writer) => writer.write_all(buf),
+            Target::File(writer) => writer.write_all(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.target {
+            Target::Stdout(writer) => writer.flush(),
+            Target::File(writer) => writer.flush(),
+        }
+    }
+}
+
+// O temporário seria criado com 0600; a saída nova recebe as permissões de um arquivo comum
+// (0666 menos a umask).
+#[cfg(unix)]
+fn output_builder() -> Builder<'static, 'static> {
+    use std::os::unix::fs::PermissionsExt;
+    let mut builder = Builder::new();
+    builder
+        .prefix(".ulp_merge_output")
+        .permissions(fs::Permissions::from_mode(0o666));
+    builder
+}
+
+#[cfg(not(unix))]
+fn output_builder() -> Builder<'static, 'static> {
+    let mut builder = Builder::new();
+    builder.prefix(".ulp_merge_output");
+    builder
+}
+
+pub(crate) fn parent_dir(path: &Path) -> &Path {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    }
+}
+
+fn already_exists(destination: &Path) -> anyhow::Error {
+    anyhow!(
+        "Arquivo de saída {:?} já existe. Use --force (ou `overwrite = \"force\"` no arquivo de configuração) para sobrescrevê-lo.",
+        destination
+    )
+}
+
+#[cfg(unix)]
+fn sync_dir(dir: &Path) {
+    if let Ok(handle) = File::open(dir) {
+        let _ = handle.sync_all();
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn replaces_destination_only_on_commit() {
+        let dir = tempdir().unwrap();
+        let output = dir.path().join("out.txt");
+        std::fs::write(&output, b"old\n").unwrap();
+
+        let mut pending = AtomicOutput::create(&output, OverwritePolicy::Force).unwrap();
+        pending.write_all(b"new\n").unwrap();
+        drop(pending);
+        assert_eq!(std::fs::read(&output).unwrap(), b"old\n");
+
+        let mut pending = AtomicOutput::create(&output, OverwritePolicy::Force).unwrap();
+        pending.write_all(b"new\n").unwrap();
+        pending.commit().unwrap();
+        assert_eq!(std::fs::read(&output).unwrap(), b"new\n");
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn keeps_regular_file_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempdir().unwrap();
+        let reference = dir.path().join("ref.txt");
+        File::create(&reference).unwrap();
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+
+        let output = dir.path().join("out.txt");
+        let pending = AtomicOutput::create(&output, OverwritePolicy::Force).unwrap();
+        pending.commit().unwrap();
+        assert_eq!(mode(&output), mode(&reference));
+
+        fs::set_permissions(&output, fs::Permissions::from_mode(0o640)).unwrap();
+        let pending = AtomicOutput::create(&output, OverwritePolicy::Force).unwrap();
+        pending.commit().unwrap();
+        assert_eq!(mode(&output), 0o640);
+    }
+
+    #[test]
+    fn no_clobber_refuses_existing_destination() {
+        let dir = tempdir().unwrap();
+        let output = dir.path().join("out.txt");
+
+        let mut pending = AtomicOutput::create(&output, OverwritePolicy::NoClobber).unwrap();
+        std::fs::write(&output, b"raced\n").unwrap();
+        pending.write_all(b"new\n").unwrap();
+        let err = pending.commit().unwrap_err();
+        assert!(format!("{err}").contains("já existe"));
+        assert_eq!(std::fs::read(&output).unwrap(), b"raced\n");
+
+        assert!(AtomicOutput::create(&output, OverwritePolicy::NoClobber).is_err());
+    }
+}
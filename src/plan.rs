@@ -0,0 +1,309 @@
+use crate::archive::{self, ArchiveKind};
+use crate::compression::InputReader;
+use crate::config::Config;
+use crate::control::CancellationToken;
+use crate::lines::{read_next_line, SourceId};
+use crate::merger::MAX_OPEN_MERGE_FILES;
+use crate::scanner::InputFile;
+use crate::summary::SkippedInput;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::io::BufRead;
+use std::path::Path;
+
+const SAMPLE_LINES: u64 = 10_000;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlannedFile {
+    pub path: String,
+    pub size: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Plan {
+    pub files: Vec<PlannedFile>,
+    pub total_bytes: u64,
+    pub unknown_sizes: usize,
+    pub avg_line_len: f64,
+    pub estimated_lines: u64,
+    pub estimated_chunks: u64,
+    pub estimated_merge_rounds: u64,
+    pub estimated_peak_temp_bytes: u64,
+    pub estimated_chunk_ram_bytes: u64,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub skipped_inputs: Vec<SkippedInput>,
+}
+
+#[derive(Debug, Default)]
+struct Sample {
+    raw_bytes: u64,
+    decoded_bytes: u64,
+    lines: u64,
+}
+
+pub fn build(config: &Config, files: &[InputFile]) -> Result<Plan> {
+    estimate(config, files, usize::MAX, &CancellationToken::new())
+}
+
+// Lê no máximo `max_samples` entradas; as demais contam só pelo tamanho, convertido em linhas
+// pela proporção das amostradas.
+pub fn estimate(
+    config: &Config,
+    files: &[InputFile],
+    max_samples: usize,
+    cancel: &CancellationToken,
+) -> Result<Plan> {
+    let mut plan = Plan::default();
+    let mut sample = Sample::default();
+    let mut estimated_lines = 0f64;
+    // Membros de pacotes: o cabeçalho dá o tamanho descomprimido, convertido em linhas pela
+    // média da amostra no fim.
+    let mut member_bytes = 0u64;
+    let mut unsampled_bytes = 0u64;
+    let mut sampled_files = 0usize;
+    let mut first_member: Option<(&Path, ArchiveKind, usize)> = None;
+
+    for file in files {
+        cancel.check()?;
+        let size = match file {
+            // FIFOs e substituições de processo não têm tamanho, e amostrá-los consumiria
+            // dados que a leitura não veria de novo.
+            InputFile::Path(path) => std::fs::metadata(path)
+                .ok()
+                .filter(|m| m.is_file())
+                .map(|m| m.len()),
+            InputFile::Member { size, .. } => Some(*size),
+            InputFile::Stdin => None,
+        };
+        plan.files.push(PlannedFile {
+            path: file.display_path().display().to_string(),
+            size,
+        });
+
+        let Some(size) = size else {
+            plan.unknown_sizes += 1;
+            continue;
+        };
+        plan.total_bytes += size;
+        let path = match file {
+            InputFile::Path(path) => path,
+            InputFile::Member {
+                archive,
+                kind,
+                index,
+                ..
+            } => {
+                member_bytes += size;
+                first_member.get_or_insert((archive, *kind, *index));
+                continue;
+            }
+            InputFile::Stdin => continue,
+        };
+        if sampled_files >= max_samples {
+            unsampled_bytes += size;
+            continue;
+        }
+        sampled_files += 1;
+
+        // Arquivos comprimidos: a amostra diz quantas linhas cabem em cada byte lido do disco.
+        // Com --keep-going, uma entrada ilegível fica de fora da estimativa; a falha é
+        // registrada na leitura.
+        let file_sample = match sample_file(path) {
+            Ok(file_sample) => file_sample,
+            Err(_) if config.keep_going => continue,
+            Err(err) => return Err(err),
+        };
+        if file_sample.raw_bytes > 0 {
+            estimated_lines +=
+                size as f64 * file_sample.lines as f64 / file_sample.raw_bytes as f64;
+        }
+        sample.raw_bytes += file_sample.raw_bytes;
+        sample.decoded_bytes += file_sample.decoded_bytes;
+        sample.lines += file_sample.lines;
+    }
+
+    // Só há membros: a média vem do primeiro deles.
+    if sample.lines == 0 {
+        if let Some((archive, kind, index)) = first_member {
+            match sample_member(archive, kind, index) {
+                Ok(member_sample) => sample = member_sample,
+                Err(_) if config.keep_going => {}
+                Err(err) => return Err(err),
+            }
+        }
+    }
+    if sample.lines > 0 {
+        plan.avg_line_len = sample.decoded_bytes as f64 / sample.lines as f64;
+        estimated_lines += member_bytes as f64 / plan.avg_line_len;
+    }
+    if sample.raw_bytes > 0 {
+        estimated_lines += unsampled_bytes as f64 * sample.lines as f64 / sample.raw_bytes as f64;
+    }
+    plan.estimated_lines = estimated_lines.ceil() as u64;
+
+    let chunk_lines = config.validated_chunk_lines() as u64;
+    plan.estimated_chunks = plan.estimated_lines.div_ceil(chunk_lines);
+    plan.estimated_merge_rounds = merge_rounds(plan.estimated_chunks);
+
+    // Ao rastrear origens, cada registro temporário carrega também um TAB e o identificador.
+    let suffix = if config.tags_sources() {
+        1 + files.len().max(1).to_string().len() as u64
+    } else {
+        0
+    };
+    let temp_bytes =
+        (plan.avg_line_len * plan.estimated_lines as f64) as u64 + suffix * plan.estimated_lines;
+    // Com rodadas intermediárias, os arquivos de uma rodada coexistem com os da seguinte.
+    plan.estimated_peak_temp_bytes = if plan.estimated_merge_rounds > 1 {
+        temp_bytes * 2
+    } else {
+        temp_bytes
+    };
+
+    let entry_overhead = std::mem::size_of::<(Vec<u8>, SourceId)>() as f64;
+    let lines_per_chunk = chunk_lines.min(plan.estimated_lines.max(1));
+    plan.estimated_chunk_ram_bytes =
+        (lines_per_chunk as f64 * (plan.avg_line_len + entry_overhead)) as u64;
+
+    Ok(plan)
+}
+
+fn sample_file(path: &Path) -> Result<Sample> {
+    let mut reader = InputReader::open(path)?;
+    let mut sample = sample_lines(&mut reader, path)?;
+    sample.raw_bytes = reader.consumed();
+    Ok(sample)
+}
+
+fn sample_member(archive: &Path, kind: ArchiveKind, index: usize) -> Result<Sample> {
+    let mut sample = Sample::default();
+    archive::for_each_member(archive, kind, &[index], |_, name, mut reader| {
+        sample = sample_lines(&mut reader, &archive::member_display_path(archive, name))?;
+        Ok(())
+    })?;
+    Ok(sample)
+}
+
+fn sample_lines(reader: &mut impl BufRead, path: &Path) -> Result<Sample> {
+    let mut sample = Sample::default();
+    while sample.lines < SAMPLE_LINES {
+        let Some(line) =
+            read_next_line(reader).with_context(|| format!("Erro ao ler linha em {:?}", path))?
+        else {
+            break;
+        };
+        sample.lines += 1;
+        sample.decoded_bytes += line.len() as u64 + 1;
+    }
+    Ok(sample)
+}
+
+fn merge_rounds(chunks: u64) -> u64 {
+    if chunks == 0 {
+        return 0;
+    }
+    let mut remaining = chunks;
+    let mut rounds = 1;
+    while remaining > MAX_OPEN_MERGE_FILES as u64 {
+        remaining = remaining.div_ceil(MAX_OPEN_MERGE_FILES as u64);
+        rounds += 1;
+    }
+    rounds
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn estimates_chunks_and_rounds_from_sample() {
+        let dir = tempdir().unwrap();
+        let input = dir.path().join("a.txt");
+        std::fs::write(&input, b"abc\nxyz\nfoo\nbar\n").unwrap();
+        let config = Config {
+            output: dir.path().join("out.txt"),
+            inputs: vec![input.clone()],
+            chunk_lines: 3,
+            quiet: true,
+            min_free_bytes: 0,
+            ..Config::default()
+        };
+
+        let files = vec![InputFile::Path(input), InputFile::Stdin];
+        let plan = build(&config, &files).unwrap();
+        assert_eq!(plan.total_bytes, 16);
+        assert_eq!(plan.unknown_sizes, 1);
+        assert_eq!(plan.avg_line_len, 4.0);
+        assert_eq!(plan.estimated_lines, 4);
+        assert_eq!(plan.estimated_chunks, 2);
+        assert_eq!(plan.estimated_merge_rounds, 1);
+        assert_eq!(plan.estimated_peak_temp_bytes, 16);
+        assert_eq!(plan.files[1].path, "<stdin>");
+    }
+
+    #[test]
+    fn estimates_archive_members_from_their_header_sizes() {
+        let dir = tempdir().unwrap();
+        let archive_path = dir.path().join("a.zip");
+        let mut writer = zip::ZipWriter::new(std::fs::File::create(&archive_path).unwrap());
+        for (name, data) in [("x.txt", &b"abc\nxyz\n"[..]), ("y.txt", b"foo\nbar\nbaz\n")] {
+            writer
+                .start_file(name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            std::io::Write::write_all(&mut writer, data).unwrap();
+        }
+        writer.finish().unwrap();
+        let config = Config {
+            output: dir.path().join("out.txt"),
+            chunk_lines: 3,
+            ..Config::default()
+        };
+
+        let files: Vec<InputFile> = archive::list_members(&archive_path, ArchiveKind::Zip)
+            .unwrap()
+            .into_iter()
+            .map(|member| InputFile::Member {
+                archive: archive_path.clone(),
+                kind: ArchiveKind::Zip,
+                name: member.name,
+                index: member.index,
+                size: member.size,
+            })
+            .collect();
+        let plan = build(&config, &files).unwrap();
+        assert_eq!(plan.total_bytes, 20);
+        assert_eq!(plan.unknown_sizes, 0);
+        assert_eq!(plan.avg_line_len, 4.0);
+        assert_eq!(plan.estimated_lines, 5);
+        assert_eq!(plan.estimated_chunks, 2);
+    }
+
+    #[test]
+    fn extrapolates_unsampled_files_from_the_sampled_ratio() {
+        let dir = tempdir().unwrap();
+        let mut files = Vec::new();
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            let input = dir.path().join(name);
+            std::fs::write(&input, b"abc\nxyz\n").unwrap();
+            files.push(InputFile::Path(input));
+        }
+        let config = Config {
+            output: dir.path().join("out.txt"),
+            ..Config::default()
+        };
+
+        let plan = estimate(&config, &files, 1, &CancellationToken::new()).unwrap();
+        assert_eq!(plan.total_bytes, 24);
+        assert_eq!(plan.estimated_lines, 6);
+        assert_eq!(plan.avg_line_len, 4.0);
+    }
+
+    #[test]
+    fn counts_intermediate_merge_rounds() {
+        assert_eq!(merge_rounds(0), 0);
+        assert_eq!(merge_rounds(64), 1);
+        assert_eq!(merge_rounds(65), 2);
+        assert_eq!(merge_rounds(64 * 64 + 1), 3);
+    }
+}
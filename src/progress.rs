@@ -1,13 +1,38 @@
+use crate::config::is_stdio;
 use std::path::Path;
 use std::time::{Duration, Instant};
 
 pub trait ProgressSink {
     fn start_file(&mut self, _path: &Path) {}
     fn on_line(&mut self) {}
+    fn on_bytes(&mut self, _consumed: u64, _total: u64) {}
     fn finish_file(&mut self, _path: &Path) {}
     fn start_merge(&mut self, _temp_count: usize) {}
     fn merge_round(&mut self, _remaining: usize) {}
     fn finish(&mut self, _output: &Path) {}
+    fn paused(&mut self) {}
+    fn resumed(&mut self) {}
+    fn skip_input(&mut self, _path: &Path, _duplicate_of: &Path) {}
+    fn retry_input(&mut self, _path: &Path, _attempt: u32) {}
+    fn fail_input(&mut self, _path: &Path, _error: &anyhow::Error, _lines_kept: u64) {}
+}
+
+// Uma entrada que falha no meio deixa na saída as linhas lidas até ali.
+pub fn failure_message(path: &Path, error: &anyhow::Error, lines_kept: u64) -> String {
+    if lines_kept == 0 {
+        format!(
+            "Entrada ignorada após falha: {}: {:#}",
+            path.display(),
+            error
+        )
+    } else {
+        format!(
+            "Entrada interrompida após falha ({} linha(s) lidas antes dela ficam na saída): {}: {:#}",
+            lines_kept,
+            path.display(),
+            error
+        )
+    }
 }
 
 pub struct ProgressReporter {
@@ -18,6 +43,8 @@ pub struct ProgressReporter {
     lines_since_tick: u64,
     last_emit: Instant,
     current_file: Option<String>,
+    current_bytes: u64,
+    current_total: u64,
 }
 
 impl ProgressReporter {
@@ -30,6 +57,8 @@ impl ProgressReporter {
             lines_since_tick: 0,
             last_emit: Instant::now(),
             current_file: None,
+            current_bytes: 0,
+            current_total: 0,
         }
     }
 
@@ -39,6 +68,24 @@ impl ProgressReporter {
             .unwrap_or("Arquivo desconhecido")
     }
 
+    fn current_percent(&self) -> String {
+        if self.current_total == 0 {
+            return String::new();
+        }
+        let pct =
+            (self.current_bytes.min(self.current_total) as f64 / self.current_total as f64) * 100.0;
+        format!(" ({:.1}%)", pct)
+    }
+
+    // Na varredura em streaming o total de arquivos ainda não é conhecido (0).
+    fn counter(&self, position: usize) -> String {
+        if self.total_files == 0 {
+            format!("[{}]", position)
+        } else {
+            format!("[{}/{}]", position, self.total_files)
+        }
+    }
+
     fn reset_tick(&mut self) {
         self.lines_since_tick = 0;
         self.last_emit = Instant::now();
@@ -51,10 +98,11 @@ impl ProgressSink for ProgressReporter {
             return;
         }
         self.current_file = Some(path.display().to_string());
+        self.current_bytes = 0;
+        self.current_total = 0;
         eprintln!(
-            "[{}/{}] Processando {}",
-            self.processed_files + 1,
-            self.total_files.max(1),
+            "{} Processando {}",
+            self.counter(self.processed_files + 1),
             self.current_filename()
         );
         self.reset_tick();
@@ -68,25 +116,29 @@ impl ProgressSink for ProgressReporter {
         self.lines_since_tick += 1;
         if self.lines_since_tick >= 100_000 || self.last_emit.elapsed() >= Duration::from_secs(2) {
             eprintln!(
-                "[{}/{}] {} — {} linhas lidas",
-                self.processed_files + 1,
-                self.total_files.max(1),
+                "{} {}{} — {} linhas lidas",
+                self.counter(self.processed_files + 1),
                 self.current_filename(),
+                self.current_percent(),
                 self.total_lines
             );
             self.reset_tick();
         }
     }
 
+    fn on_bytes(&mut self, consumed: u64, total: u64) {
+        self.current_bytes = consumed;
+        self.current_total = total;
+    }
+
     fn finish_file(&mut self, path: &Path) {
         if !self.enabled {
             return;
         }
         self.processed_files += 1;
         eprintln!(
-            "[{}/{}] Concluído {}",
-            self.processed_files,
-            self.total_files.max(1),
+            "{} Concluído {}",
+            self.counter(self.processed_files),
             path.display()
         );
         self.reset_tick();
@@ -119,11 +171,55 @@ impl ProgressSink for ProgressReporter {
         if !self.enabled {
             return;
         }
+        let destination = if is_stdio(output) {
+            "saída padrão".to_string()
+        } else {
+            output.display().to_string()
+        };
         eprintln!(
             "Processamento finalizado. Total de arquivos processados: {}. Linhas lidas: {}. Resultado salvo em {}",
             self.processed_files,
             self.total_lines,
-            output.display()
+            destination
         );
     }
+
+    fn paused(&mut self) {
+        if self.enabled {
+            eprintln!("Processamento pausado.");
+        }
+    }
+
+    fn resumed(&mut self) {
+        if !self.enabled {
+            return;
+        }
+        eprintln!("Processamento retomado.");
+        self.reset_tick();
+    }
+
+    fn skip_input(&mut self, path: &Path, duplicate_of: &Path) {
+        if self.enabled {
+            eprintln!(
+                "Entrada ignorada: {} é duplicada de {}",
+                path.display(),
+                duplicate_of.display()
+            );
+        }
+    }
+
+    fn retry_input(&mut self, path: &Path, attempt: u32) {
+        if self.enabled {
+            eprintln!(
+                "Falha ao ler {}; nova tentativa ({}ª)...",
+                path.display(),
+                attempt
+            );
+        }
+    }
+
+    // Falhas toleradas aparecem mesmo com --quiet: a saída ficará incompleta.
+    fn fail_input(&mut self, path: &Path, error: &anyhow::Error, lines_kept: u64) {
+        eprintln!("{}", failure_message(path, error, lines_kept));
+    }
 }
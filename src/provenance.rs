@@ -0,0 +1,270 @@
+use crate::config::OverwritePolicy;
+use crate::lines::{read_next_line, SourceId};
+use crate::output::AtomicOutput;
+use anyhow::{anyhow, Context, Result};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+
+const MAGIC: &[u8; 8] = b"ULPPROV\x01";
+
+pub fn sidecar_path(output: &Path) -> PathBuf {
+    let mut name = output.as_os_str().to_owned();
+    name.push(".prov");
+    PathBuf::from(name)
+}
+
+pub struct ProvenanceWriter {
+    out: AtomicOutput,
+}
+
+impl ProvenanceWriter {
+    pub fn create(path: &Path, overwrite: OverwritePolicy, files: &[String]) -> Result<Self> {
+        let mut out = AtomicOutput::create(path, overwrite)?;
+        let header = (|| -> io::Result<()> {
+            out.write_all(MAGIC)?;
+            write_varint(&mut out, files.len() as u64)?;
+            for name in files {
+                write_varint(&mut out, name.len() as u64)?;
+                out.write_all(name.as_bytes())?;
+            }
+            Ok(())
+        })();
+        header
+            .with_context(|| format!("Falha ao escrever cabeçalho de proveniência {:?}", path))?;
+        Ok(Self { out })
+    }
+
+    pub fn record(&mut self, sources: &[SourceId]) -> io::Result<()> {
+        write_varint(&mut self.out, sources.len() as u64)?;
+        let mut previous = 0;
+        for id in sources {
+            write_varint(&mut self.out, u64::from(id - previous))?;
+            previous = *id;
+        }
+        Ok(())
+    }
+
+    pub fn commit(self) -> Result<()> {
+        self.out.commit()
+    }
+}
+
+pub struct ProvenanceReader {
+    files: Vec<String>,
+    reader: BufReader<File>,
+    next_line: u64,
+}
+
+impl ProvenanceReader {
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = File::open(path)
+            .with_context(|| format!("Falha ao abrir arquivo de proveniência {:?}", path))?;
+        let file_len = file
+            .metadata()
+            .with_context(|| format!("Falha ao abrir arquivo de proveniência {:?}", path))?
+            .len();
+        let mut reader = BufReader::new(file);
+
+        let mut magic = [0u8; 8];
+        reader
+            .read_exact(&mut magic)
+            .ok()
+            .filter(|_| &magic == MAGIC)
+            .ok_or_else(|| anyhow!("{:?} não é um arquivo de proveniência válido", path))?;
+
+        // Os tamanhos vêm do próprio arquivo: um valor maior que o que resta dele indica
+        // corrupção, e não pode virar uma alocação.
+        let count = read_varint(&mut reader)?.unwrap_or(0);
+        if count > remaining(&mut reader, file_len)? {
+            return Err(anyhow!("Tabela de arquivos inválida em {:?}", path));
+        }
+        let mut files = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let len = read_varint(&mut reader)?.unwrap_or(0);
+            if len > remaining(&mut reader, file_len)? {
+                return Err(anyhow!("Tabela de arquivos truncada em {:?}", path));
+            }
+            let mut name = vec![0u8; len as usize];
+            reader
+                .read_exact(&mut name)
+                .with_context(|| format!("Tabela de arquivos truncada em {:?}", path))?;
+            files.push(String::from_utf8_lossy(&name).into_owned());
+        }
+
+        Ok(Self {
+            files,
+            reader,
+            next_line: 1,
+        })
+    }
+
+    pub fn files(&self) -> &[String] {
+        &self.files
+    }
+
+    // Linhas são numeradas a partir de 1 e só podem ser consultadas em ordem crescente.
+    pub fn sources_of(&mut self, line_number: u64) -> Result<Option<Vec<&str>>> {
+        if line_number < self.next_line {
+            return Err(anyhow!(
+                "Consulta fora de ordem: linha {} já foi percorrida",
+                line_number
+            ));
+        }
+
+        loop {
+            let Some(count) = read_varint(&mut self.reader)? else {
+                return Ok(None);
+            };
+            let current = self.next_line;
+            self.next_line += 1;
+
+            // Identificadores não se repetem numa linha: nunca há mais que os da tabela.
+            if count > self.files.len() as u64 {
+                return Err(anyhow!(
+                    "Registro de proveniência inválido na linha {}",
+                    current
+                ));
+            }
+            let mut ids = Vec::with_capacity(count as usize);
+            let mut previous = 0u64;
+            for _ in 0..count {
+                let delta = read_varint(&mut self.reader)?
+                    .ok_or_else(|| anyhow!("Registro de proveniência truncado"))?;
+                previous += delta;
+                ids.push(previous);
+            }
+
+            if current == line_number {
+                return ids
+                    .into_iter()
+                    .map(|id| {
+                        self.files
+                            .get(id as usize)
+                            .map(String::as_str)
+                            .ok_or_else(|| anyhow!("Identificador de arquivo inválido: {}", id))
+                    })
+                    .collect::<Result<Vec<_>>>()
+                    .map(Some);
+            }
+        }
+    }
+}
+
+pub fn find_line_number(output: &Path, needle: &[u8]) -> Result<Option<u64>> {
+    let file = File::open(output)
+        .with_context(|| format!("Falha ao abrir arquivo de saída {:?}", output))?;
+    let mut reader = BufReader::new(file);
+    let mut number = 0u64;
+
+    while let Some(line) =
+        read_next_line(&mut reader).with_context(|| format!("Erro ao ler linha em {:?}", output))?
+    {
+        number += 1;
+        match line.as_slice().cmp(needle) {
+            std::cmp::Ordering::Less => continue,
+            std::cmp::Ordering::Equal => return Ok(Some(number)),
+            // A saída é ordenada: passou do ponto, a linha não existe.
+            std::cmp::Ordering::Greater => return Ok(None),
+        }
+    }
+
+    Ok(None)
+}
+
+fn remaining(reader: &mut BufReader<File>, file_len: u64) -> Result<u64> {
+    let position = reader
+        .stream_position()
+        .context("Erro ao ler arquivo de proveniência")?;
+    Ok(file_len.saturating_sub(position))
+}
+
+fn write_varint<W: Write>(writer: &mut W, mut value: u64) -> io::Result<()> {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            return writer.write_all(&[byte]);
+        }
+        writer.write_all(&[byte | 0x80])?;
+    }
+}
+
+fn read_varint<R: BufRead>(reader: &mut R) -> Result<Option<u64>> {
+    let mut value = 0u64;
+    let mut shift = 0;
+    loop {
+        let mut byte = [0u8; 1];
+        if reader
+            .read(&mut byte)
+            .context("Erro ao ler arquivo de proveniência")?
+            == 0
+        {
+            if shift == 0 {
+                return Ok(None);
+            }
+            return Err(anyhow!("Registro de proveniência truncado"));
+        }
+        value |= u64::from(byte[0] & 0x7f) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(Some(value));
+        }
+        shift += 7;
+        if shift >= 64 {
+            return Err(anyhow!("Inteiro inválido no arquivo de proveniência"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn writes_and_queries_sidecar() {
+        let dir = tempdir().unwrap();
+        let output = dir.path().join("out.txt");
+        std::fs::write(&output, b"a\nb\nc\n").unwrap();
+        let sidecar = sidecar_path(&output);
+        assert_eq!(sidecar, dir.path().join("out.txt.prov"));
+
+        let files = vec!["x.txt".to_string(), "y.txt".to_string()];
+        let mut writer =
+            ProvenanceWriter::create(&sidecar, OverwritePolicy::NoClobber, &files).unwrap();
+        writer.record(&[0]).unwrap();
+        writer.record(&[0, 1]).unwrap();
+        writer.record(&[1]).unwrap();
+        writer.commit().unwrap();
+
+        assert_eq!(find_line_number(&output, b"b").unwrap(), Some(2));
+        assert_eq!(find_line_number(&output, b"bb").unwrap(), None);
+
+        let mut reader = ProvenanceReader::open(&sidecar).unwrap();
+        assert_eq!(reader.files(), files.as_slice());
+        assert_eq!(reader.sources_of(2).unwrap(), Some(vec!["x.txt", "y.txt"]));
+        assert_eq!(reader.sources_of(3).unwrap(), Some(vec!["y.txt"]));
+        assert_eq!(reader.sources_of(4).unwrap(), None);
+    }
+
+    #[test]
+    fn rejects_sizes_larger_than_the_file() {
+        let dir = tempdir().unwrap();
+        let sidecar = dir.path().join("out.txt.prov");
+        let mut huge = Vec::new();
+        write_varint(&mut huge, u64::MAX >> 1).unwrap();
+
+        let corrupt_table = [&MAGIC[..], &huge].concat();
+        std::fs::write(&sidecar, corrupt_table).unwrap();
+        assert!(ProvenanceReader::open(&sidecar).is_err());
+
+        let corrupt_name = [&MAGIC[..], &[1], &huge, b"x"].concat();
+        std::fs::write(&sidecar, corrupt_name).unwrap();
+        assert!(ProvenanceReader::open(&sidecar).is_err());
+
+        let corrupt_record = [&MAGIC[..], &[1, 1], b"x", &huge].concat();
+        std::fs::write(&sidecar, corrupt_record).unwrap();
+        let mut reader = ProvenanceReader::open(&sidecar).unwrap();
+        assert!(reader.sources_of(1).is_err());
+    }
+}
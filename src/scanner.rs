@@ -1,69 +1,581 @@
-use crate::config::Config;
+use crate::archive::{self, ArchiveKind};
+use crate::compression::strip_compressed_suffix;
+use crate::config::{is_stdio, Config, STDIO_PATH};
+use crate::control::CancellationToken;
 use anyhow::{anyhow, Context, Result};
-use std::fs;
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use ignore::{DirEntry, WalkBuilder, WalkState};
+use std::fs::{self, Metadata};
 use std::path::{Path, PathBuf};
-use walkdir::WalkDir;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::SystemTime;
 
-pub fn collect_input_files(config: &Config) -> Result<Vec<PathBuf>> {
-    let mut files = Vec::new();
+pub const STDIN_LABEL: &str = "<stdin>";
+pub const IGNORE_FILE: &str = ".ulpignore";
 
-    for input in &config.inputs {
-        if input.is_dir() {
-            if config.recursive {
-                collect_recursive(input, &config.output, &config.ext, &mut files)?;
-            } else {
-                collect_shallow(input, &config.output, &config.ext, &mut files)?;
-            }
-        } else if input.is_file() {
-            if has_matching_ext(input, &config.ext) && !same_file(input, &config.output) {
-                files.push(input.to_path_buf());
-            }
+// Recebe cada lote de entradas encontradas (um arquivo ou todos os membros de um pacote).
+// Devolver `false` interrompe a varredura.
+pub type Emit<'a> = dyn Fn(Vec<InputFile>) -> bool + Sync + 'a;
+// Consultada a cada entrada visitada; `true` interrompe a varredura.
+pub type Stop<'a> = dyn Fn() -> bool + Sync + 'a;
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum InputFile {
+    Stdin,
+    Path(PathBuf),
+    Member {
+        archive: PathBuf,
+        kind: ArchiveKind,
+        name: String,
+        index: usize,
+        size: u64,
+    },
+}
+
+impl InputFile {
+    pub fn display_path(&self) -> PathBuf {
+        match self {
+            InputFile::Stdin => PathBuf::from(STDIN_LABEL),
+            InputFile::Path(path) => path.clone(),
+            InputFile::Member { archive, name, .. } => archive::member_display_path(archive, name),
+        }
+    }
+}
+
+// Critérios aplicados a cada arquivo encontrado. Os padrões de --include/--exclude são
+// comparados com o caminho relativo à entrada varrida (ou ao arquivo compactado, no caso de
+// membros).
+struct Filter<'a> {
+    output: &'a Path,
+    output_exists: bool,
+    output_id: Option<(u64, u64)>,
+    extensions: &'a [String],
+    include: Option<GlobSet>,
+    exclude: Option<GlobSet>,
+    skip_hidden: bool,
+    ignore_files: bool,
+    recursive: bool,
+    max_depth: Option<usize>,
+    follow_symlinks: bool,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    modified_after: Option<SystemTime>,
+    modified_before: Option<SystemTime>,
+    threads: usize,
+}
+
+impl<'a> Filter<'a> {
+    fn new(config: &'a Config) -> Result<Self> {
+        // A saída é identificada uma única vez; cada arquivo encontrado é comparado pelo
+        // inode, sem canonicalizar caminhos.
+        let output_meta = fs::metadata(&config.output).ok();
+        Ok(Self {
+            output: &config.output,
+            output_exists: output_meta.is_some(),
+            output_id: output_meta.as_ref().and_then(file_identity),
+            extensions: &config.extensions,
+            include: build_globset(&config.include, "--include")?,
+            exclude: build_globset(&config.exclude, "--exclude")?,
+            skip_hidden: config.skip_hidden,
+            ignore_files: config.ignore_files,
+            recursive: config.recursive,
+            max_depth: config.max_depth,
+            follow_symlinks: config.follow_symlinks,
+            min_size: config.min_size,
+            max_size: config.max_size,
+            modified_after: config.modified_after.as_ref().map(|t| t.at),
+            modified_before: config.modified_before.as_ref().map(|t| t.at),
+            threads: config.scan_threads,
+        })
+    }
+
+    fn is_output(&self, path: &Path, meta: &Metadata) -> bool {
+        if !self.output_exists {
+            return false;
+        }
+        match (self.output_id, file_identity(meta)) {
+            (Some(output), Some(id)) => output == id,
+            _ => same_file(path, self.output),
+        }
+    }
+
+    fn has_metadata_filters(&self) -> bool {
+        self.min_size.is_some()
+            || self.max_size.is_some()
+            || self.modified_after.is_some()
+            || self.modified_before.is_some()
+    }
+
+    // Tamanho e data de modificação são os do arquivo no disco; para pacotes .zip/.tar valem
+    // os do próprio pacote.
+    fn accepts_metadata(&self, path: &Path, meta: &Metadata) -> Result<bool> {
+        if !self.has_metadata_filters() {
+            return Ok(true);
+        }
+        let size = meta.len();
+        if self.min_size.is_some_and(|min| size < min)
+            || self.max_size.is_some_and(|max| size > max)
+        {
+            return Ok(false);
+        }
+        if self.modified_after.is_none() && self.modified_before.is_none() {
+            return Ok(true);
+        }
+        let modified = meta
+            .modified()
+            .with_context(|| format!("Data de modificação indisponível para {:?}", path))?;
+        Ok(self.modified_after.is_none_or(|after| modified >= after)
+            && self.modified_before.is_none_or(|before| modified <= before))
+    }
+
+    fn accepts(&self, relative: &Path) -> bool {
+        has_matching_ext(relative, self.extensions)
+            && self
+                .include
+                .as_ref()
+                .is_none_or(|include| include.is_match(relative))
+    }
+
+    // Profundidade máxima abaixo da entrada: sem --recursive só o primeiro nível.
+    fn depth_limit(&self) -> Option<usize> {
+        if self.recursive {
+            self.max_depth
         } else {
-            return Err(anyhow!("Caminho inválido: {:?}", input));
+            Some(1)
         }
     }
 
-    if files.is_empty() {
-        return Err(anyhow!(
-            "Nenhum arquivo de entrada encontrado com a extensão informada"
-        ));
+    // Percurso com as regras comuns a diretórios e padrões: .ulpignore, --skip-hidden e a
+    // política de links simbólicos.
+    fn walker(&self, root: &Path, max_depth: Option<usize>) -> WalkBuilder {
+        let mut builder = WalkBuilder::new(root);
+        builder
+            .standard_filters(false)
+            .hidden(self.skip_hidden)
+            .max_depth(max_depth)
+            .follow_links(self.follow_symlinks);
+        if self.ignore_files {
+            builder.add_custom_ignore_filename(IGNORE_FILE);
+        }
+        builder
     }
 
+    // Vale para arquivos e diretórios: um diretório descartado não é percorrido.
+    fn skips(&self, relative: &Path) -> bool {
+        (self.skip_hidden && is_hidden(relative))
+            || self
+                .exclude
+                .as_ref()
+                .is_some_and(|exclude| exclude.is_match(relative))
+    }
+}
+
+fn build_globset(patterns: &[String], option: &str) -> Result<Option<GlobSet>> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(
+            GlobBuilder::new(pattern)
+                .literal_separator(true)
+                .build()
+                .with_context(|| format!("Padrão de {} inválido: {:?}", option, pattern))?,
+        );
+    }
+    let set = builder
+        .build()
+        .with_context(|| format!("Falha ao compilar padrões de {}", option))?;
+    Ok(Some(set))
+}
+
+fn is_hidden(relative: &Path) -> bool {
+    relative
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with('.'))
+}
+
+pub fn collect_input_files(config: &Config, cancel: &CancellationToken) -> Result<Vec<InputFile>> {
+    let found = Mutex::new(Vec::new());
+    scan_inputs(config, &|| cancel.is_cancelled(), &|batch| {
+        found.lock().unwrap().extend(batch);
+        true
+    })?;
+    cancel.check()?;
+    let mut files = found.into_inner().unwrap();
+    if files.is_empty() {
+        return Err(no_inputs_error());
+    }
     files.sort();
     Ok(files)
 }
 
-fn collect_recursive(input: &Path, output: &Path, ext: &str, acc: &mut Vec<PathBuf>) -> Result<()> {
-    for entry in WalkDir::new(input) {
-        let entry = entry.with_context(|| format!("Falha ao percorrer diretório {:?}", input))?;
-        let path = entry.path();
-        if path.is_file() && has_matching_ext(path, ext) && !same_file(path, output) {
-            acc.push(path.to_path_buf());
+pub(crate) fn no_inputs_error() -> anyhow::Error {
+    anyhow!("Nenhum arquivo de entrada encontrado com as extensões e filtros informados")
+}
+
+// Entrega as entradas a `emit` à medida que são encontradas, na ordem em que a varredura
+// paralela as descobre. Os caminhos informados são todos validados antes de começar.
+pub fn scan_inputs(config: &Config, stop: &Stop, emit: &Emit) -> Result<()> {
+    let filter = Filter::new(config)?;
+    for root in resolve_roots(config, &filter)? {
+        if stop() {
+            break;
+        }
+        let keep_going = match root {
+            None => emit(vec![InputFile::Stdin]),
+            Some(path) => collect_path(&path, &filter, stop, emit)?,
+        };
+        if !keep_going {
+            break;
         }
     }
     Ok(())
 }
 
-fn collect_shallow(input: &Path, output: &Path, ext: &str, acc: &mut Vec<PathBuf>) -> Result<()> {
-    let dir_iter =
-        fs::read_dir(input).with_context(|| format!("Falha ao ler diretório {:?}", input))?;
-    for entry in dir_iter {
-        let entry = entry?;
-        let path = entry.path();
-        if path.is_file() && has_matching_ext(&path, ext) && !same_file(&path, output) {
-            acc.push(path);
+// `None` representa a entrada padrão.
+fn resolve_roots(config: &Config, filter: &Filter) -> Result<Vec<Option<PathBuf>>> {
+    let mut roots = Vec::new();
+    let mut stdin_seen = false;
+    // Entradas inexistentes são reunidas e informadas de uma vez, o que importa quando a lista
+    // vem de um arquivo com milhares de caminhos.
+    let mut missing = Vec::new();
+
+    for input in &config.inputs {
+        if is_stdio(input) {
+            if stdin_seen {
+                return Err(anyhow!(
+                    "A entrada padrão ({}) só pode ser informada uma vez",
+                    STDIO_PATH
+                ));
+            }
+            stdin_seen = true;
+            roots.push(None);
+        } else if input.exists() {
+            roots.push(Some(input.clone()));
+        } else if is_glob(input) {
+            let matches = expand_glob(input, filter)?;
+            if matches.is_empty() {
+                missing.push(format!(
+                    "{:?} (nenhum caminho corresponde ao padrão)",
+                    input
+                ));
+            }
+            roots.extend(matches.into_iter().map(Some));
+        } else {
+            missing.push(format!("{:?}", input));
+        }
+    }
+
+    if !missing.is_empty() {
+        return Err(missing_inputs_error(&missing));
+    }
+    Ok(roots)
+}
+
+fn collect_path(input: &Path, filter: &Filter, stop: &Stop, emit: &Emit) -> Result<bool> {
+    if input.is_dir() {
+        return collect_dir(input, filter, stop, emit);
+    }
+    let mut found = Vec::new();
+    if input.is_file() {
+        // --exclude e --skip-hidden valem só para o que a varredura encontra: um arquivo
+        // informado pelo nome é sempre considerado.
+        let relative = input.file_name().map(Path::new).unwrap_or(input);
+        collect_file(input, relative, filter, &mut found)?;
+    } else if is_stream(input) {
+        // Pipes nomeados e substituição de processo (/dev/fd/N) não têm extensão
+        // nem podem ser relidos: entram como estão, sem filtro.
+        found.push(InputFile::Path(input.to_path_buf()));
+    } else {
+        return Err(anyhow!("Caminho inválido: {:?}", input));
+    }
+    Ok(found.is_empty() || emit(found))
+}
+
+// Percurso único e paralelo para diretórios: sem --recursive só o primeiro nível é lido; com
+// ele, até --max-depth. Os arquivos .ulpignore e a opção --skip-hidden são aplicados pelo
+// próprio percurso. Ao seguir links simbólicos, um link que aponta para um diretório
+// ancestral é ignorado em vez de repetir a árvore indefinidamente.
+fn collect_dir(input: &Path, filter: &Filter, stop: &Stop, emit: &Emit) -> Result<bool> {
+    let mut builder = filter.walker(input, filter.depth_limit());
+    builder.threads(filter.threads);
+
+    let failure: Mutex<Option<anyhow::Error>> = Mutex::new(None);
+    let stopped = AtomicBool::new(false);
+    builder.build_parallel().run(|| {
+        Box::new(|entry| {
+            if stop() {
+                return WalkState::Quit;
+            }
+            match visit_entry(input, entry, filter, emit) {
+                Ok(Some(state)) => state,
+                Ok(None) => {
+                    stopped.store(true, Ordering::Relaxed);
+                    WalkState::Quit
+                }
+                Err(err) => {
+                    failure.lock().unwrap().get_or_insert(err);
+                    WalkState::Quit
+                }
+            }
+        })
+    });
+
+    match failure.into_inner().unwrap() {
+        Some(err) => Err(err),
+        None => Ok(!stopped.load(Ordering::Relaxed)),
+    }
+}
+
+// Devolve `None` quando `emit` pede para interromper a varredura.
+fn visit_entry(
+    input: &Path,
+    entry: Result<DirEntry, ignore::Error>,
+    filter: &Filter,
+    emit: &Emit,
+) -> Result<Option<WalkState>> {
+    let entry = match entry {
+        Ok(entry) => entry,
+        Err(err) if is_loop(&err) => return Ok(Some(WalkState::Continue)),
+        Err(err) => {
+            return Err(err).with_context(|| format!("Falha ao percorrer diretório {:?}", input))
+        }
+    };
+    if let Some(err) = entry.error() {
+        return Err(anyhow!("Regras inválidas em {}: {}", IGNORE_FILE, err));
+    }
+    if entry.depth() == 0 {
+        return Ok(Some(WalkState::Continue));
+    }
+    let path = entry.path();
+    let relative = path.strip_prefix(input).unwrap_or(path);
+    let is_dir = entry.file_type().is_some_and(|kind| kind.is_dir());
+    if filter.skips(relative) {
+        // Um diretório descartado não é percorrido.
+        return Ok(Some(if is_dir {
+            WalkState::Skip
+        } else {
+            WalkState::Continue
+        }));
+    }
+    if is_dir {
+        return Ok(Some(WalkState::Continue));
+    }
+    let mut found = Vec::new();
+    collect_file(path, relative, filter, &mut found)?;
+    Ok((found.is_empty() || emit(found)).then_some(WalkState::Continue))
+}
+
+fn is_loop(err: &ignore::Error) -> bool {
+    match err {
+        ignore::Error::Loop { .. } => true,
+        ignore::Error::WithPath { err, .. }
+        | ignore::Error::WithDepth { err, .. }
+        | ignore::Error::WithLineNumber { err, .. } => is_loop(err),
+        ignore::Error::Partial(errs) => errs.iter().any(is_loop),
+        _ => false,
+    }
+}
+
+fn collect_file(
+    path: &Path,
+    relative: &Path,
+    filter: &Filter,
+    acc: &mut Vec<InputFile>,
+) -> Result<()> {
+    // Links quebrados e entradas sem permissão de leitura dos metadados são ignorados.
+    let Ok(meta) = fs::metadata(path) else {
+        return Ok(());
+    };
+    if !meta.is_file() || filter.is_output(path, &meta) || !filter.accepts_metadata(path, &meta)? {
+        return Ok(());
+    }
+
+    if let Some(kind) = archive::archive_kind(path) {
+        collect_archive(path, kind, filter, acc)
+    } else {
+        if filter.accepts(relative) {
+            acc.push(InputFile::Path(path.to_path_buf()));
+        }
+        Ok(())
+    }
+}
+
+fn collect_archive(
+    path: &Path,
+    kind: ArchiveKind,
+    filter: &Filter,
+    acc: &mut Vec<InputFile>,
+) -> Result<()> {
+    for member in archive::list_members(path, kind)? {
+        let relative = member.name.trim_start_matches("./");
+        let depth = Path::new(relative).components().count();
+        if filter.depth_limit().is_some_and(|limit| depth > limit) {
+            continue;
+        }
+        let skipped = Path::new(relative)
+            .ancestors()
+            .filter(|part| !part.as_os_str().is_empty())
+            .any(|part| filter.skips(part));
+        if !skipped && filter.accepts(Path::new(relative)) {
+            acc.push(InputFile::Member {
+                archive: path.to_path_buf(),
+                kind,
+                name: member.name.clone(),
+                index: member.index,
+                size: member.size,
+            });
         }
     }
     Ok(())
 }
 
-fn has_matching_ext(path: &Path, ext: &str) -> bool {
-    match path.extension().and_then(|e| e.to_str()) {
-        Some(e) => e.eq_ignore_ascii_case(ext),
+fn has_matching_ext(path: &Path, extensions: &[String]) -> bool {
+    match strip_compressed_suffix(path)
+        .extension()
+        .and_then(|e| e.to_str())
+    {
+        Some(e) => extensions
+            .iter()
+            .any(|ext| e.eq_ignore_ascii_case(ext.trim_start_matches('.'))),
         None => false,
     }
 }
 
+fn is_glob(path: &Path) -> bool {
+    path.to_str()
+        .is_some_and(|text| text.contains(['*', '?', '[', '{']))
+}
+
+// Expande um padrão passado como entrada (útil quando o shell não expande `**`). A busca parte
+// do maior prefixo sem curingas e segue as mesmas regras da varredura de diretórios
+// (.ulpignore, --exclude, --skip-hidden, links simbólicos e, com `**`, --max-depth);
+// diretórios que casam entram inteiros, como se fossem informados diretamente.
+fn expand_glob(pattern: &Path, filter: &Filter) -> Result<Vec<PathBuf>> {
+    let text = pattern
+        .to_str()
+        .ok_or_else(|| anyhow!("Padrão inválido: {:?}", pattern))?;
+    let matcher = GlobBuilder::new(text)
+        .literal_separator(true)
+        .build()
+        .with_context(|| format!("Padrão inválido: {:?}", pattern))?
+        .compile_matcher();
+
+    let mut base = PathBuf::new();
+    let mut depth = 0;
+    let mut unbounded = false;
+    for component in pattern.components() {
+        let part = Path::new(component.as_os_str());
+        if depth == 0 && !is_glob(part) {
+            base.push(component);
+        } else {
+            depth += 1;
+            unbounded |= part.to_string_lossy().contains("**");
+        }
+    }
+
+    let root = if base.as_os_str().is_empty() {
+        PathBuf::from(".")
+    } else {
+        base.clone()
+    };
+    let max_depth = if unbounded {
+        filter.max_depth
+    } else {
+        Some(depth)
+    };
+    let mut builder = filter.walker(&root, max_depth);
+    builder.sort_by_file_name(|a, b| a.cmp(b));
+
+    let mut matches = Vec::new();
+    // Diretório descartado ou já incluído inteiro: o que vem abaixo dele não é considerado.
+    // O percurso é em profundidade, então basta comparar com o último.
+    let mut pruned: Option<PathBuf> = None;
+    for entry in builder.build() {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) if is_loop(&err) => continue,
+            Err(err) => {
+                return Err(err).with_context(|| format!("Falha ao percorrer diretório {:?}", root))
+            }
+        };
+        if let Some(err) = entry.error() {
+            return Err(anyhow!("Regras inválidas em {}: {}", IGNORE_FILE, err));
+        }
+        if entry.depth() == 0
+            || pruned
+                .as_ref()
+                .is_some_and(|dir| entry.path().starts_with(dir))
+        {
+            continue;
+        }
+        let is_dir = entry.file_type().is_some_and(|kind| kind.is_dir());
+        let relative = entry.path().strip_prefix(&root).unwrap_or(entry.path());
+        if filter.skips(relative) {
+            if is_dir {
+                pruned = Some(entry.path().to_path_buf());
+            }
+            continue;
+        }
+        let path = if base.as_os_str().is_empty() {
+            entry.path().strip_prefix(".").unwrap_or(entry.path())
+        } else {
+            entry.path()
+        };
+        if matcher.is_match(path) {
+            matches.push(path.to_path_buf());
+            if is_dir {
+                pruned = Some(entry.path().to_path_buf());
+            }
+        }
+    }
+
+    Ok(matches)
+}
+
+fn missing_inputs_error(missing: &[String]) -> anyhow::Error {
+    const SHOWN: usize = 20;
+    let mut message = format!("Entradas não encontradas ({}):", missing.len());
+    for entry in missing.iter().take(SHOWN) {
+        message.push_str("\n  ");
+        message.push_str(entry);
+    }
+    if missing.len() > SHOWN {
+        message.push_str(&format!("\n  ... e mais {}", missing.len() - SHOWN));
+    }
+    anyhow!(message)
+}
+
+#[cfg(unix)]
+fn is_stream(path: &Path) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+    fs::metadata(path)
+        .map(|meta| {
+            let file_type = meta.file_type();
+            file_type.is_fifo() || file_type.is_char_device() || file_type.is_socket()
+        })
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_stream(_path: &Path) -> bool {
+    false
+}
+
+#[cfg(unix)]
+pub(crate) fn file_identity(meta: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((meta.dev(), meta.ino()))
+}
+
+#[cfg(not(unix))]
+pub(crate) fn file_identity(_meta: &Metadata) -> Option<(u64, u64)> {
+    None
+}
+
 fn same_file(a: &Path, b: &Path) -> bool {
     let ca = fs::canonicalize(a).unwrap_or_else(|_| a.to_path_buf());
     let cb = fs::canonicalize(b).unwrap_or_else(|_| b.to_path_buf());
@@ -75,17 +587,22 @@ mod tests {
     use super::*;
     use crate::config::Config;
     use std::fs::File;
+    use std::io::Write;
     use tempfile::tempdir;
 
+    fn collect(config: &Config) -> Result<Vec<InputFile>> {
+        collect_input_files(config, &CancellationToken::new())
+    }
+
     fn build_config(inputs: Vec<PathBuf>, output: PathBuf, recursive: bool) -> Config {
         Config {
             output,
             inputs,
-            ext: "txt".into(),
             recursive,
             chunk_lines: 10,
-            temp_dir: None,
             quiet: true,
+            min_free_bytes: 0,
+            ..Config::default()
         }
     }
 
@@ -106,8 +623,14 @@ mod tests {
             dir.path().join("out.txt"),
             true,
         );
-        let files = collect_input_files(&config).unwrap();
-        assert_eq!(files, vec![file_a.clone(), file_b.clone()]);
+        let files = collect(&config).unwrap();
+        assert_eq!(
+            files,
+            vec![
+                InputFile::Path(file_a.clone()),
+                InputFile::Path(file_b.clone())
+            ]
+        );
     }
 
     #[test]
@@ -117,7 +640,370 @@ mod tests {
         let output = input.clone();
         File::create(&input).unwrap();
         let config = build_config(vec![dir.path().to_path_buf()], output, false);
-        let err = collect_input_files(&config).unwrap_err();
+        let err = collect(&config).unwrap_err();
         assert!(format!("{err}").contains("Nenhum arquivo"));
     }
+
+    #[test]
+    fn filters_by_extensions_and_include_globs() {
+        let dir = tempdir().unwrap();
+        let sub = dir.path().join("leaks");
+        fs::create_dir_all(&sub).unwrap();
+        let top = dir.path().join("passwords.txt");
+        let nested = sub.join("passwords_2024.csv");
+        let other = sub.join("emails.txt");
+        for path in [&top, &nested, &other] {
+            File::create(path).unwrap();
+        }
+        File::create(sub.join("notes.md")).unwrap();
+
+        let mut config = build_config(
+            vec![dir.path().to_path_buf()],
+            dir.path().join("out.txt"),
+            true,
+        );
+        config.extensions = vec!["txt".into(), ".CSV".into()];
+        assert_eq!(
+            collect(&config).unwrap(),
+            vec![
+                InputFile::Path(other),
+                InputFile::Path(nested.clone()),
+                InputFile::Path(top.clone())
+            ]
+        );
+
+        config.include = vec!["**/passwords*".into()];
+        assert_eq!(
+            collect(&config).unwrap(),
+            vec![InputFile::Path(nested.clone()), InputFile::Path(top)]
+        );
+
+        config.include = vec!["leaks/*.csv".into()];
+        assert_eq!(collect(&config).unwrap(), vec![InputFile::Path(nested)]);
+
+        config.include = vec!["[".into()];
+        assert!(collect(&config).is_err());
+    }
+
+    #[test]
+    fn skips_excluded_ignored_and_hidden_entries() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().join("dados");
+        for sub in ["keep", "lixo/deep", "logs", ".cache"] {
+            fs::create_dir_all(root.join(sub)).unwrap();
+        }
+        let kept = root.join("keep").join("a.txt");
+        let whitelisted = root.join("logs").join("important.txt");
+        for path in [
+            &kept,
+            &whitelisted,
+            &root.join("lixo").join("deep").join("b.txt"),
+            &root.join("logs").join("debug.txt"),
+            &root.join(".cache").join("c.txt"),
+            &root.join(".hidden.txt"),
+        ] {
+            File::create(path).unwrap();
+        }
+        fs::write(root.join(IGNORE_FILE), "logs/*.txt\n").unwrap();
+        fs::write(root.join("logs").join(IGNORE_FILE), "!important.txt\n").unwrap();
+
+        let mut config = build_config(vec![root.clone()], dir.path().join("out.txt"), true);
+        config.exclude = vec!["lixo".into()];
+        config.skip_hidden = true;
+        assert_eq!(
+            collect(&config).unwrap(),
+            vec![InputFile::Path(kept.clone()), InputFile::Path(whitelisted)]
+        );
+
+        config.skip_hidden = false;
+        config.ignore_files = false;
+        config.exclude = vec!["**/deep".into(), "logs".into()];
+        assert_eq!(collect(&config).unwrap().len(), 3);
+
+        fs::write(root.join(IGNORE_FILE), "*.txt\n").unwrap();
+        let mut shallow = build_config(vec![root], dir.path().join("out.txt"), false);
+        assert!(collect(&shallow).is_err());
+        shallow.ignore_files = false;
+        assert_eq!(collect(&shallow).unwrap().len(), 1);
+
+        // Arquivos informados pelo nome não passam por --exclude nem --skip-hidden.
+        let hidden = dir.path().join("dados").join(".hidden.txt");
+        let mut named = build_config(vec![hidden.clone()], dir.path().join("out.txt"), false);
+        named.skip_hidden = true;
+        named.exclude = vec!["*.txt".into()];
+        assert_eq!(collect(&named).unwrap(), vec![InputFile::Path(hidden)]);
+    }
+
+    #[test]
+    fn limits_depth_and_filters_by_size_and_mtime() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().join("dados");
+        fs::create_dir_all(root.join("a").join("b")).unwrap();
+        let top = root.join("top.txt");
+        let mid = root.join("a").join("mid.txt");
+        let deep = root.join("a").join("b").join("deep.txt");
+        fs::write(&top, "1\n").unwrap();
+        fs::write(&mid, "linha maior\n").unwrap();
+        fs::write(&deep, "2\n").unwrap();
+
+        let mut config = build_config(vec![root.clone()], dir.path().join("out.txt"), true);
+        config.max_depth = Some(2);
+        assert_eq!(
+            collect(&config).unwrap(),
+            vec![InputFile::Path(mid.clone()), InputFile::Path(top.clone())]
+        );
+
+        config.max_depth = None;
+        config.min_size = Some(5);
+        assert_eq!(
+            collect(&config).unwrap(),
+            vec![InputFile::Path(mid.clone())]
+        );
+
+        config.min_size = None;
+        config.max_size = Some(4);
+        let week = std::time::Duration::from_secs(7 * 24 * 60 * 60);
+        let old = SystemTime::now() - week * 2;
+        File::options()
+            .write(true)
+            .open(&deep)
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+        config.modified_after = Some((SystemTime::now() - week).into());
+        assert_eq!(collect(&config).unwrap(), vec![InputFile::Path(top)]);
+
+        config.modified_after = None;
+        config.modified_before = Some((SystemTime::now() - week).into());
+        assert_eq!(collect(&config).unwrap(), vec![InputFile::Path(deep)]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn follows_symlinks_without_looping() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().join("dados");
+        let real = dir.path().join("real");
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::create_dir_all(&real).unwrap();
+        let inner = root.join("sub").join("a.txt");
+        File::create(&inner).unwrap();
+        File::create(real.join("b.txt")).unwrap();
+        std::os::unix::fs::symlink(&real, root.join("link")).unwrap();
+        std::os::unix::fs::symlink(&root, root.join("sub").join("loop")).unwrap();
+
+        let mut config = build_config(vec![root.clone()], dir.path().join("out.txt"), true);
+        assert_eq!(
+            collect(&config).unwrap(),
+            vec![InputFile::Path(inner.clone())]
+        );
+
+        config.follow_symlinks = true;
+        assert_eq!(
+            collect(&config).unwrap(),
+            vec![
+                InputFile::Path(root.join("link").join("b.txt")),
+                InputFile::Path(inner)
+            ]
+        );
+    }
+
+    #[test]
+    fn streams_batches_and_stops_on_request() {
+        let dir = tempdir().unwrap();
+        for i in 0..50 {
+            File::create(dir.path().join(format!("{i}.txt"))).unwrap();
+        }
+        let mut config = build_config(
+            vec![dir.path().to_path_buf()],
+            dir.path().join("out.txt"),
+            false,
+        );
+        config.scan_threads = 4;
+        assert_eq!(collect(&config).unwrap().len(), 50);
+
+        let received = Mutex::new(0);
+        scan_inputs(&config, &|| false, &|batch| {
+            let mut received = received.lock().unwrap();
+            *received += batch.len();
+            *received < 3
+        })
+        .unwrap();
+        // Threads que já estavam com um arquivo em mãos ainda podem entregá-lo.
+        assert!(received.into_inner().unwrap() < 50);
+    }
+
+    #[test]
+    fn cancelled_scan_reports_cancellation() {
+        let dir = tempdir().unwrap();
+        File::create(dir.path().join("a.txt")).unwrap();
+        let config = build_config(
+            vec![dir.path().to_path_buf()],
+            dir.path().join("out.txt"),
+            false,
+        );
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let err = collect_input_files(&config, &cancel).unwrap_err();
+        assert!(err.downcast_ref::<crate::control::Cancelled>().is_some());
+    }
+
+    #[test]
+    fn expands_glob_inputs() {
+        let dir = tempdir().unwrap();
+        let a = dir.path().join("2023");
+        let b = dir.path().join("2024").join("deep");
+        fs::create_dir_all(&a).unwrap();
+        fs::create_dir_all(&b).unwrap();
+        let first = a.join("dump.txt");
+        let second = b.join("dump.txt");
+        File::create(&first).unwrap();
+        File::create(&second).unwrap();
+        File::create(dir.path().join("dump.txt.bak")).unwrap();
+
+        let pattern = dir.path().join("**").join("dump.txt");
+        let config = build_config(vec![pattern], dir.path().join("out.txt"), false);
+        assert_eq!(
+            collect(&config).unwrap(),
+            vec![
+                InputFile::Path(first.clone()),
+                InputFile::Path(second.clone())
+            ]
+        );
+
+        // Diretórios que casam com o padrão são varridos como entradas comuns.
+        let pattern = dir.path().join("202[3]");
+        let config = build_config(vec![pattern], dir.path().join("out.txt"), false);
+        assert_eq!(
+            collect(&config).unwrap(),
+            vec![InputFile::Path(first.clone())]
+        );
+
+        let pattern = dir.path().join("*.csv");
+        let config = build_config(vec![pattern], dir.path().join("out.txt"), false);
+        let err = collect(&config).unwrap_err();
+        assert!(format!("{err}").contains("nenhum caminho corresponde"));
+
+        // A expansão segue o .ulpignore, o --exclude e o --max-depth da varredura.
+        let pattern = dir.path().join("**").join("dump.txt");
+        fs::write(dir.path().join(IGNORE_FILE), "2023/\n").unwrap();
+        let mut config = build_config(vec![pattern], dir.path().join("out.txt"), false);
+        assert_eq!(
+            collect(&config).unwrap(),
+            vec![InputFile::Path(second.clone())]
+        );
+        config.exclude = vec!["2024".into()];
+        assert!(collect(&config).is_err());
+        config.exclude.clear();
+        config.max_depth = Some(2);
+        assert!(collect(&config).is_err());
+        config.ignore_files = false;
+        assert_eq!(collect(&config).unwrap(), vec![InputFile::Path(first)]);
+    }
+
+    #[test]
+    fn reports_every_missing_input() {
+        let dir = tempdir().unwrap();
+        let existing = dir.path().join("a.txt");
+        File::create(&existing).unwrap();
+        let mut inputs = vec![existing];
+        inputs.extend((0..25).map(|i| dir.path().join(format!("ausente{i}.txt"))));
+        let config = build_config(inputs, dir.path().join("out.txt"), false);
+
+        let message = format!("{}", collect(&config).unwrap_err());
+        assert!(message.starts_with("Entradas não encontradas (25):"));
+        assert!(message.contains("ausente0.txt"));
+        assert!(message.contains("ausente19.txt"));
+        assert!(!message.contains("ausente20.txt"));
+        assert!(message.ends_with("... e mais 5"));
+    }
+
+    #[test]
+    fn matches_inner_extension_of_compressed_files() {
+        let dir = tempdir().unwrap();
+        let gz = dir.path().join("a.txt.gz");
+        let zst = dir.path().join("b.TXT.zst");
+        File::create(&gz).unwrap();
+        File::create(&zst).unwrap();
+        File::create(dir.path().join("c.csv.gz")).unwrap();
+        File::create(dir.path().join("d.gz")).unwrap();
+
+        let config = build_config(
+            vec![dir.path().to_path_buf()],
+            dir.path().join("out.txt"),
+            false,
+        );
+        let files = collect(&config).unwrap();
+        assert_eq!(files, vec![InputFile::Path(gz), InputFile::Path(zst)]);
+    }
+
+    #[test]
+    fn accepts_stdin_once_and_named_pipes() {
+        let dir = tempdir().unwrap();
+        let output = dir.path().join("out.txt");
+
+        let config = build_config(vec![PathBuf::from("-")], output.clone(), false);
+        assert_eq!(collect(&config).unwrap(), vec![InputFile::Stdin]);
+
+        let config = build_config(
+            vec![PathBuf::from("-"), PathBuf::from("-")],
+            output.clone(),
+            false,
+        );
+        assert!(collect(&config).is_err());
+
+        #[cfg(unix)]
+        {
+            let fifo = dir.path().join("pipe");
+            let status = std::process::Command::new("mkfifo")
+                .arg(&fifo)
+                .status()
+                .unwrap();
+            assert!(status.success());
+            let config = build_config(vec![fifo.clone()], output, false);
+            assert_eq!(collect(&config).unwrap(), vec![InputFile::Path(fifo)]);
+        }
+    }
+
+    #[test]
+    fn expands_archive_members_like_directories() {
+        let dir = tempdir().unwrap();
+        let archive_path = dir.path().join("bundle.zip");
+        let mut writer = zip::ZipWriter::new(File::create(&archive_path).unwrap());
+        for name in ["top.txt", "nested/inner.txt", "skip.csv"] {
+            writer
+                .start_file(name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(b"x\n").unwrap();
+        }
+        writer.finish().unwrap();
+
+        let member = |name: &str, index: usize| InputFile::Member {
+            archive: archive_path.clone(),
+            kind: ArchiveKind::Zip,
+            name: name.to_string(),
+            index,
+            size: 2,
+        };
+
+        let shallow = build_config(
+            vec![dir.path().to_path_buf()],
+            dir.path().join("out.txt"),
+            false,
+        );
+        assert_eq!(collect(&shallow).unwrap(), vec![member("top.txt", 0)]);
+
+        let mut recursive =
+            build_config(vec![archive_path.clone()], dir.path().join("out.txt"), true);
+        assert_eq!(
+            collect(&recursive).unwrap(),
+            vec![member("nested/inner.txt", 1), member("top.txt", 0)]
+        );
+        recursive.max_depth = Some(1);
+        assert_eq!(collect(&recursive).unwrap(), vec![member("top.txt", 0)]);
+        assert_eq!(
+            archive::member_display_path(&archive_path, "nested/inner.txt"),
+            PathBuf::from(format!("{}!/nested/inner.txt", archive_path.display()))
+        );
+    }
 }
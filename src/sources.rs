@@ -0,0 +1,283 @@
+use crate::config::OverlapGrouping;
+use crate::lines::SourceId;
+use crate::provenance::ProvenanceWriter;
+use crate::scanner::InputFile;
+use crate::summary::{OverlapGroup, OverlapPair, OverlapRelation, OverlapReport, SourceStats};
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::{Component, PathBuf};
+
+#[derive(Debug, Clone, Copy, Default)]
+struct SourceCounts {
+    unique: u64,
+    exclusive: u64,
+}
+
+pub struct SourceTracker {
+    counts: Vec<SourceCounts>,
+    overlap: Option<OverlapTracker>,
+    provenance: Option<ProvenanceWriter>,
+}
+
+impl SourceTracker {
+    pub fn new(source_count: usize) -> Self {
+        Self {
+            counts: vec![SourceCounts::default(); source_count],
+            overlap: None,
+            provenance: None,
+        }
+    }
+
+    pub fn with_overlap(mut self, overlap: OverlapTracker) -> Self {
+        self.overlap = Some(overlap);
+        self
+    }
+
+    pub fn with_provenance(mut self, provenance: ProvenanceWriter) -> Self {
+        self.provenance = Some(provenance);
+        self
+    }
+
+    pub fn observe(&mut self, sources: &[SourceId]) -> Result<()> {
+        let exclusive = sources.len() == 1;
+        for id in sources {
+            if let Some(counts) = self.counts.get_mut(*id as usize) {
+                counts.unique += 1;
+                if exclusive {
+                    counts.exclusive += 1;
+                }
+            }
+        }
+        if let Some(overlap) = self.overlap.as_mut() {
+            overlap.observe(sources);
+        }
+        if let Some(provenance) = self.provenance.as_mut() {
+            provenance
+                .record(sources)
+                .context("Erro ao escrever arquivo de proveniência")?;
+        }
+        Ok(())
+    }
+
+    pub fn take_provenance(&mut self) -> Option<ProvenanceWriter> {
+        self.provenance.take()
+    }
+
+    pub fn take_overlap(&mut self) -> Option<OverlapReport> {
+        self.overlap.take().map(OverlapTracker::into_report)
+    }
+
+    pub fn into_stats(self, names: Vec<String>, lines_read: &[u64]) -> Vec<SourceStats> {
+        names
+            .into_iter()
+            .zip(self.counts)
+            .enumerate()
+            .map(|(idx, (source, counts))| SourceStats {
+                source,
+                lines_read: lines_read.get(idx).copied().unwrap_or(0),
+                unique_lines: counts.unique,
+                exclusive_lines: counts.exclusive,
+            })
+            .collect()
+    }
+}
+
+pub struct OverlapTracker {
+    group_of: Vec<u32>,
+    names: Vec<String>,
+    unique: Vec<u64>,
+    shared: HashMap<(u32, u32), u64>,
+    scratch: Vec<u32>,
+}
+
+impl OverlapTracker {
+    pub fn new(files: &[InputFile], inputs: &[PathBuf], grouping: OverlapGrouping) -> Self {
+        let mut names: Vec<String> = Vec::new();
+        let mut index: HashMap<String, u32> = HashMap::new();
+        let group_of = files
+            .iter()
+            .map(|file| {
+                let label = group_label(file, inputs, grouping);
+                *index.entry(label.clone()).or_insert_with(|| {
+                    names.push(label);
+                    (names.len() - 1) as u32
+                })
+            })
+            .collect();
+
+        Self {
+            group_of,
+            unique: vec![0; names.len()],
+            names,
+            shared: HashMap::new(),
+            scratch: Vec::new(),
+        }
+    }
+
+    fn observe(&mut self, sources: &[SourceId]) {
+        self.scratch.clear();
+        self.scratch.extend(
+            sources
+                .iter()
+                .filter_map(|id| self.group_of.get(*id as usize).copied()),
+        );
+        self.scratch.sort_unstable();
+        self.scratch.dedup();
+
+        for (pos, a) in self.scratch.iter().enumerate() {
+            self.unique[*a as usize] += 1;
+            for b in &self.scratch[pos + 1..] {
+                *self.shared.entry((*a, *b)).or_insert(0) += 1;
+            }
+        }
+    }
+
+    fn into_report(self) -> OverlapReport {
+        let mut pairs: Vec<OverlapPair> = self
+            .shared
+            .into_iter()
+            .map(|((a, b), shared)| {
+                let a_unique = self.unique[a as usize];
+                let b_unique = self.unique[b as usize];
+                let relation = if shared == a_unique && shared == b_unique {
+                    OverlapRelation::Duplicate
+                } else if shared == a_unique {
+                    OverlapRelation::ASubsetOfB
+                } else if shared == b_unique {
+                    OverlapRelation::BSubsetOfA
+                } else {
+                    OverlapRelation::Partial
+                };
+                OverlapPair {
+                    a: self.names[a as usize].clone(),
+                    b: self.names[b as usize].clone(),
+                    shared,
+                    a_unique,
+                    b_unique,
+                    relation,
+                }
+            })
+            .collect();
+        pairs.sort_by(|x, y| (&x.a, &x.b).cmp(&(&y.a, &y.b)));
+
+        OverlapReport {
+            groups: self
+                .names
+                .into_iter()
+                .zip(self.unique)
+                .map(|(name, unique_lines)| OverlapGroup { name, unique_lines })
+                .collect(),
+            pairs,
+        }
+    }
+}
+
+fn group_label(file: &InputFile, inputs: &[PathBuf], grouping: OverlapGrouping) -> String {
+    let path = match (grouping, file) {
+        (OverlapGrouping::File, _) | (_, InputFile::Stdin) => {
+            return file.display_path().display().to_string()
+        }
+        (OverlapGrouping::TopDir, InputFile::Path(path)) => path,
+        (OverlapGrouping::TopDir, InputFile::Member { archive, name, .. }) => {
+            // Pacote informado diretamente: o diretório de primeiro nível é o de dentro dele.
+            if inputs.iter().any(|input| input == archive) {
+                return match name.trim_start_matches("./").split_once('/') {
+                    Some((top, _)) => format!("{}!/{}", archive.display(), top),
+                    None => archive.display().to_string(),
+                };
+            }
+            archive
+        }
+    };
+
+    let top_dir = inputs.iter().find_map(|root| {
+        let relative = path.strip_prefix(root).ok()?;
+        let mut components = relative
+            .components()
+            .filter(|c| matches!(c, Component::Normal(_)));
+        let first = components.next()?;
+        Some(match components.next() {
+            Some(_) => root.join(first),
+            None => root.to_path_buf(),
+        })
+    });
+
+    top_dir
+        .unwrap_or_else(|| path.to_path_buf())
+        .display()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_unique_and_exclusive_lines_per_source() {
+        let mut tracker = SourceTracker::new(2);
+        tracker.observe(&[0]).unwrap();
+        tracker.observe(&[0, 1]).unwrap();
+        tracker.observe(&[1]).unwrap();
+        tracker.observe(&[1]).unwrap();
+
+        let stats = tracker.into_stats(vec!["a.txt".into(), "b.txt".into()], &[3, 5]);
+        assert_eq!(stats[0].unique_lines, 2);
+        assert_eq!(stats[0].exclusive_lines, 1);
+        assert_eq!(stats[1].lines_read, 5);
+        assert_eq!(stats[1].unique_lines, 3);
+        assert_eq!(stats[1].exclusive_lines, 2);
+    }
+
+    #[test]
+    fn groups_by_top_level_directory_and_flags_relations() {
+        let root = PathBuf::from("data");
+        let files = vec![
+            InputFile::Path(PathBuf::from("data/a/1.txt")),
+            InputFile::Path(PathBuf::from("data/a/deep/2.txt")),
+            InputFile::Path(PathBuf::from("data/b/3.txt")),
+            InputFile::Path(PathBuf::from("data/top.txt")),
+            InputFile::Path(PathBuf::from("solo.txt")),
+        ];
+        let inputs = vec![root, PathBuf::from("solo.txt")];
+        let overlap = OverlapTracker::new(&files, &inputs, OverlapGrouping::TopDir);
+        assert_eq!(overlap.names, vec!["data/a", "data/b", "data", "solo.txt"]);
+        assert_eq!(overlap.group_of, vec![0, 0, 1, 2, 3]);
+
+        let mut tracker = SourceTracker::new(files.len()).with_overlap(overlap);
+        tracker.observe(&[0, 2]).unwrap();
+        tracker.observe(&[1, 2, 4]).unwrap();
+        tracker.observe(&[0]).unwrap();
+        tracker.observe(&[3, 4]).unwrap();
+
+        let report = tracker.take_overlap().unwrap();
+        let groups: Vec<(&str, u64)> = report
+            .groups
+            .iter()
+            .map(|g| (g.name.as_str(), g.unique_lines))
+            .collect();
+        assert_eq!(
+            groups,
+            vec![("data/a", 3), ("data/b", 2), ("data", 1), ("solo.txt", 2)]
+        );
+        let relation = |a: &str, b: &str| {
+            report
+                .pairs
+                .iter()
+                .find(|p| p.a == a && p.b == b)
+                .map(|p| (p.shared, p.relation))
+        };
+        assert_eq!(
+            relation("data/a", "data/b"),
+            Some((2, OverlapRelation::BSubsetOfA))
+        );
+        assert_eq!(
+            relation("data", "solo.txt"),
+            Some((1, OverlapRelation::ASubsetOfB))
+        );
+        assert_eq!(
+            relation("data/b", "solo.txt"),
+            Some((1, OverlapRelation::Partial))
+        );
+        assert_eq!(relation("data/a", "data"), None);
+    }
+}
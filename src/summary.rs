@@ -0,0 +1,328 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::Duration;
+
+const HISTOGRAM_BOUNDS: [usize; 8] = [8, 16, 32, 64, 128, 256, 512, 1024];
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RunSummary {
+    pub files_processed: usize,
+    pub lines_read: u64,
+    pub unique_lines_written: u64,
+    pub duplicates_removed: u64,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    pub chunks: usize,
+    pub merge_rounds: usize,
+    pub timings: PhaseTimings,
+    pub peak_temp_bytes: u64,
+    pub line_length_histogram: LineLengthHistogram,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<SourceStats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub overlap: Option<OverlapReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delta: Option<DeltaStats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub incremental: Option<IncrementalStats>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub skipped_inputs: Vec<SkippedInput>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub failed_inputs: Vec<InputFailure>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SkippedInput {
+    pub path: String,
+    pub duplicate_of: String,
+    pub reason: DuplicateReason,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateReason {
+    SameFile,
+    SameContent,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InputFailure {
+    pub path: String,
+    pub reason: String,
+    pub attempts: u32,
+    // Linhas lidas antes da falha: não são descartadas e ficam na saída.
+    pub lines_kept: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct DeltaStats {
+    pub added: u64,
+    pub removed: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct IncrementalStats {
+    pub previous_lines: u64,
+    pub added: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct OverlapReport {
+    pub groups: Vec<OverlapGroup>,
+    pub pairs: Vec<OverlapPair>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OverlapGroup {
+    pub name: String,
+    pub unique_lines: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OverlapPair {
+    pub a: String,
+    pub b: String,
+    pub shared: u64,
+    pub a_unique: u64,
+    pub b_unique: u64,
+    pub relation: OverlapRelation,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverlapRelation {
+    Partial,
+    ASubsetOfB,
+    BSubsetOfA,
+    Duplicate,
+}
+
+impl OverlapRelation {
+    fn as_str(self) -> &'static str {
+        match self {
+            OverlapRelation::Partial => "partial",
+            OverlapRelation::ASubsetOfB => "a_subset_of_b",
+            OverlapRelation::BSubsetOfA => "b_subset_of_a",
+            OverlapRelation::Duplicate => "duplicate",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SourceStats {
+    pub source: String,
+    pub lines_read: u64,
+    pub unique_lines: u64,
+    pub exclusive_lines: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct PhaseTimings {
+    pub scan_secs: f64,
+    pub chunk_secs: f64,
+    pub merge_secs: f64,
+    pub total_secs: f64,
+}
+
+impl PhaseTimings {
+    pub fn new(scan: Duration, chunk: Duration, merge: Duration) -> Self {
+        Self {
+            scan_secs: scan.as_secs_f64(),
+            chunk_secs: chunk.as_secs_f64(),
+            merge_secs: merge.as_secs_f64(),
+            total_secs: (scan + chunk + merge).as_secs_f64(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HistogramBucket {
+    pub max_len: Option<usize>,
+    pub count: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(transparent)]
+pub struct LineLengthHistogram {
+    buckets: Vec<HistogramBucket>,
+}
+
+impl Default for LineLengthHistogram {
+    fn default() -> Self {
+        let mut buckets: Vec<HistogramBucket> = HISTOGRAM_BOUNDS
+            .iter()
+            .map(|bound| HistogramBucket {
+                max_len: Some(bound - 1),
+                count: 0,
+            })
+            .collect();
+        buckets.push(HistogramBucket {
+            max_len: None,
+            count: 0,
+        });
+        Self { buckets }
+    }
+}
+
+impl LineLengthHistogram {
+    pub fn record(&mut self, len: usize) {
+        let idx = HISTOGRAM_BOUNDS
+            .iter()
+            .position(|bound| len < *bound)
+            .unwrap_or(HISTOGRAM_BOUNDS.len());
+        self.buckets[idx].count += 1;
+    }
+
+    pub fn buckets(&self) -> &[HistogramBucket] {
+        &self.buckets
+    }
+}
+
+pub fn write_source_stats(path: &Path, stats: &[SourceStats]) -> Result<()> {
+    let file = File::create(path).with_context(|| {
+        format!(
+            "Não foi possível criar arquivo de estatísticas por entrada {:?}",
+            path
+        )
+    })?;
+    let mut writer = BufWriter::new(file);
+
+    if is_csv(path) {
+        writeln!(writer, "source,lines_read,unique_lines,exclusive_lines")?;
+        for row in stats {
+            writeln!(
+                writer,
+                "{},{},{},{}",
+                csv_field(&row.source),
+                row.lines_read,
+                row.unique_lines,
+                row.exclusive_lines
+            )?;
+        }
+    } else {
+        serde_json::to_writer_pretty(&mut writer, stats)
+            .context("Falha ao serializar estatísticas por entrada")?;
+        writer.write_all(b"\n")?;
+    }
+
+    writer
+        .flush()
+        .with_context(|| format!("Falha ao gravar estatísticas por entrada {:?}", path))
+}
+
+pub fn write_overlap(path: &Path, report: &OverlapReport) -> Result<()> {
+    let file = File::create(path)
+        .with_context(|| format!("Não foi possível criar arquivo de sobreposição {:?}", path))?;
+    let mut writer = BufWriter::new(file);
+
+    if is_csv(path) {
+        writeln!(writer, "a,b,shared,a_unique,b_unique,relation")?;
+        for pair in &report.pairs {
+            writeln!(
+                writer,
+                "{},{},{},{},{},{}",
+                csv_field(&pair.a),
+                csv_field(&pair.b),
+                pair.shared,
+                pair.a_unique,
+                pair.b_unique,
+                pair.relation.as_str()
+            )?;
+        }
+    } else {
+        serde_json::to_writer_pretty(&mut writer, report)
+            .context("Falha ao serializar relatório de sobreposição")?;
+        writer.write_all(b"\n")?;
+    }
+
+    writer
+        .flush()
+        .with_context(|| format!("Falha ao gravar relatório de sobreposição {:?}", path))
+}
+
+pub fn write_failures(path: &Path, failures: &[InputFailure]) -> Result<()> {
+    let file = File::create(path)
+        .with_context(|| format!("Não foi possível criar relatório de falhas {:?}", path))?;
+    let mut writer = BufWriter::new(file);
+
+    if is_csv(path) {
+        writeln!(writer, "path,attempts,lines_kept,reason")?;
+        for failure in failures {
+            writeln!(
+                writer,
+                "{},{},{},{}",
+                csv_field(&failure.path),
+                failure.attempts,
+                failure.lines_kept,
+                csv_field(&failure.reason)
+            )?;
+        }
+    } else {
+        serde_json::to_writer_pretty(&mut writer, failures)
+            .context("Falha ao serializar relatório de falhas")?;
+        writer.write_all(b"\n")?;
+    }
+
+    writer
+        .flush()
+        .with_context(|| format!("Falha ao gravar relatório de falhas {:?}", path))
+}
+
+fn is_csv(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("csv"))
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buckets_line_lengths_by_power_of_two() {
+        let mut histogram = LineLengthHistogram::default();
+        for len in [0, 7, 8, 15, 1023, 1024, 50_000] {
+            histogram.record(len);
+        }
+        let counts: Vec<u64> = histogram.buckets().iter().map(|b| b.count).collect();
+        assert_eq!(counts, vec![2, 2, 0, 0, 0, 0, 0, 1, 2]);
+        assert_eq!(histogram.buckets()[0].max_len, Some(7));
+        assert_eq!(histogram.buckets()[8].max_len, None);
+    }
+
+    #[test]
+    fn writes_source_stats_as_csv() {
+        let dir = tempfile::tempdir().unwrap();
+        let csv = dir.path().join("sources.csv");
+        let stats = vec![
+            SourceStats {
+                source: "a.txt".into(),
+                lines_read: 3,
+                unique_lines: 2,
+                exclusive_lines: 1,
+            },
+            SourceStats {
+                source: "b,c.txt".into(),
+                lines_read: 5,
+                unique_lines: 3,
+                exclusive_lines: 2,
+            },
+        ];
+        write_source_stats(&csv, &stats).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&csv).unwrap(),
+            "source,lines_read,unique_lines,exclusive_lines\na.txt,3,2,1\n\"b,c.txt\",5,3,2\n"
+        );
+    }
+}
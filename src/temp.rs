@@ -1,31 +1,50 @@
+use crate::config::{is_stdio, TempPlacement};
+use crate::disk::{self, SpaceGuard};
 use anyhow::{anyhow, Context, Result};
+use std::cell::{Cell, RefCell};
 use std::fs;
+use std::io::{self, BufWriter, Write};
 use std::path::{Path, PathBuf};
 use tempfile::{Builder, NamedTempFile};
 
 pub struct TempFileFactory {
-    primary: PathBuf,
+    dirs: Vec<PathBuf>,
     fallback: Option<PathBuf>,
+    placement: TempPlacement,
+    next: Cell<usize>,
+    full: RefCell<Vec<bool>>,
+    live_bytes: Cell<u64>,
+    peak_bytes: Cell<u64>,
+    guard: Option<SpaceGuard>,
 }
 
 impl TempFileFactory {
-    pub fn new(preferred: Option<&Path>, output: &Path) -> Result<Self> {
-        let primary = preferred.map(|dir| dir.to_path_buf()).unwrap_or_else(|| {
-            output
-                .parent()
-                .map(|p| p.to_path_buf())
-                .unwrap_or_else(|| PathBuf::from("."))
-        });
-
-        if !primary.exists() {
-            fs::create_dir_all(&primary).with_context(|| {
-                format!("Não foi possível criar diretório temporário {:?}", primary)
-            })?;
+    pub fn new(preferred: &[PathBuf], output: &Path) -> Result<Self> {
+        let dirs = if preferred.is_empty() {
+            vec![if is_stdio(output) {
+                std::env::temp_dir()
+            } else {
+                output
+                    .parent()
+                    .filter(|p| !p.as_os_str().is_empty())
+                    .map(|p| p.to_path_buf())
+                    .unwrap_or_else(|| PathBuf::from("."))
+            }]
+        } else {
+            preferred.to_vec()
+        };
+
+        for dir in &dirs {
+            if !dir.exists() {
+                fs::create_dir_all(dir).with_context(|| {
+                    format!("Não foi possível criar diretório temporário {:?}", dir)
+                })?;
+            }
         }
 
-        let fallback = if preferred.is_none() {
+        let fallback = if preferred.is_empty() {
             let system_temp = std::env::temp_dir();
-            if system_temp != primary {
+            if system_temp != dirs[0] {
                 Some(system_temp)
             } else {
                 None
@@ -40,39 +59,181 @@ impl TempFileFactory {
             }
         }
 
-        Ok(Self { primary, fallback })
+        Ok(Self {
+            full: RefCell::new(vec![false; dirs.len()]),
+            dirs,
+            fallback,
+            placement: TempPlacement::RoundRobin,
+            next: Cell::new(0),
+            live_bytes: Cell::new(0),
+            peak_bytes: Cell::new(0),
+            guard: None,
+        })
+    }
+
+    pub fn with_placement(mut self, placement: TempPlacement) -> Self {
+        self.placement = placement;
+        self
+    }
+
+    pub fn with_space_guard(mut self, guard: SpaceGuard) -> Self {
+        self.guard = Some(guard);
+        self
+    }
+
+    pub fn dirs(&self) -> &[PathBuf] {
+        &self.dirs
+    }
+
+    // Diretórios que caem abaixo da reserva saem da rotação como os cheios; o processamento só
+    // para quando não sobra nenhum.
+    pub fn ensure_space(&self) -> Result<()> {
+        let Some(guard) = self.guard.as_ref() else {
+            return Ok(());
+        };
+        let mut full = self.full.borrow_mut();
+        guard.check(&mut full)?;
+        if self.fallback.is_none() && full.iter().all(|full| *full) {
+            return Err(anyhow!(
+                "Espaço livre abaixo da reserva mínima de {} em todos os diretórios temporários ({}). Processamento interrompido antes de encher o disco.",
+                disk::format_bytes(guard.reserve()),
+                self.dirs
+                    .iter()
+                    .map(|dir| format!("{:?}", dir))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn track_written(&self, tmp: &NamedTempFile) -> u64 {
+        let size = tmp.as_file().metadata().map(|m| m.len()).unwrap_or(0);
+        let live = self.live_bytes.get() + size;
+        self.live_bytes.set(live);
+        self.peak_bytes.set(self.peak_bytes.get().max(live));
+        size
+    }
+
+    pub fn track_released(&self, bytes: u64) {
+        self.live_bytes
+            .set(self.live_bytes.get().saturating_sub(bytes));
     }
 
+    pub fn peak_usage(&self) -> u64 {
+        self.peak_bytes.get()
+    }
+
+    #[cfg(test)]
     pub fn create(&self) -> Result<NamedTempFile> {
-        match Self::create_in(&self.primary) {
-            Ok(file) => Ok(file),
-            Err(primary_err) => {
-                if let Some(ref fallback) = self.fallback {
-                    Self::create_in(fallback).map_err(|fallback_err| {
-                        anyhow!(
-                            "Não foi possível criar arquivo temporário em {:?} ({}) nem em {:?} ({}). Use --temp-dir para apontar um local com espaço disponível.",
-                            self.primary,
-                            primary_err,
-                            fallback,
-                            fallback_err
-                        )
-                    })
-                } else {
-                    Err(anyhow!(
-                        "Não foi possível criar arquivo temporário em {:?}: {}. Use --temp-dir para apontar um local com espaço disponível.",
-                        self.primary,
-                        primary_err
-                    ))
+        self.create_avoiding(&[]).map(|(tmp, _)| tmp)
+    }
+
+    // Cria o temporário e grava o conteúdo com `fill`. Se o disco escolhido encher no meio da
+    // escrita, ele é marcado como cheio e a escrita recomeça no próximo diretório.
+    pub fn write_temp<F>(&self, avoid: &[&Path], mut fill: F) -> Result<NamedTempFile>
+    where
+        F: FnMut(&mut BufWriter<&mut NamedTempFile>) -> Result<()>,
+    {
+        loop {
+            let (mut tmp, dir) = self.create_avoiding(avoid)?;
+            let written = {
+                let mut writer = BufWriter::new(&mut tmp);
+                fill(&mut writer).and_then(|_| {
+                    writer
+                        .flush()
+                        .context("Erro ao finalizar escrita de arquivo temporário")
+                })
+            };
+            match written {
+                Ok(()) => {
+                    self.track_written(&tmp);
+                    return Ok(tmp);
                 }
+                Err(err) if is_storage_full(&err) => {
+                    let Some(idx) = dir else {
+                        return Err(err);
+                    };
+                    self.full.borrow_mut()[idx] = true;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    // Devolve também o índice do diretório usado (`None` para o diretório de reserva). Só um
+    // disco sem espaço fica marcado como cheio; outras falhas apenas passam ao próximo.
+    fn create_avoiding(&self, avoid: &[&Path]) -> Result<(NamedTempFile, Option<usize>)> {
+        self.ensure_space()?;
+
+        let mut errors: Vec<String> = Vec::new();
+        for idx in self.candidates(avoid) {
+            match Self::create_in(&self.dirs[idx]) {
+                Ok(file) => return Ok((file, Some(idx))),
+                Err(err) => {
+                    if err.kind() == io::ErrorKind::StorageFull {
+                        self.full.borrow_mut()[idx] = true;
+                    }
+                    errors.push(format!("{:?} ({})", self.dirs[idx], err));
+                }
+            }
+        }
+
+        if let Some(ref fallback) = self.fallback {
+            match Self::create_in(fallback) {
+                Ok(file) => return Ok((file, None)),
+                Err(err) => errors.push(format!("{:?} ({})", fallback, err)),
             }
         }
+
+        Err(anyhow!(
+            "Não foi possível criar arquivo temporário em {}. Use --temp-dir para apontar um local com espaço disponível.",
+            if errors.is_empty() {
+                "nenhum diretório disponível (todos estão cheios)".to_string()
+            } else {
+                errors.join(", ")
+            }
+        ))
+    }
+
+    // Ordem de tentativa: diretórios cheios ficam de fora; os que guardam os arquivos lidos no
+    // momento vão para o fim, para que leitura e escrita caiam em discos diferentes.
+    fn candidates(&self, avoid: &[&Path]) -> Vec<usize> {
+        let full = self.full.borrow();
+        let mut order: Vec<usize> = match self.placement {
+            TempPlacement::RoundRobin => {
+                let start = self.next.get() % self.dirs.len();
+                self.next.set(start + 1);
+                (0..self.dirs.len())
+                    .map(|offset| (start + offset) % self.dirs.len())
+                    .collect()
+            }
+            TempPlacement::FreeSpace => {
+                let mut by_space: Vec<(usize, u64)> = (0..self.dirs.len())
+                    .map(|idx| (idx, disk::available_space(&self.dirs[idx]).unwrap_or(0)))
+                    .collect();
+                by_space.sort_by_key(|(_, available)| std::cmp::Reverse(*available));
+                by_space.into_iter().map(|(idx, _)| idx).collect()
+            }
+        };
+        order.retain(|idx| !full[*idx]);
+        order.sort_by_key(|idx| avoid.contains(&self.dirs[*idx].as_path()));
+        order
     }
 
-    fn create_in(dir: &Path) -> std::io::Result<NamedTempFile> {
+    fn create_in(dir: &Path) -> io::Result<NamedTempFile> {
         Builder::new().prefix("ulp_merge_chunk").tempfile_in(dir)
     }
 }
 
+fn is_storage_full(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        cause
+            .downcast_ref::<io::Error>()
+            .is_some_and(|io_err| io_err.kind() == io::ErrorKind::StorageFull)
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -84,8 +245,80 @@ mod tests {
         let custom = dir.path().join("custom_tmp");
         let output = dir.path().join("out.txt");
         assert!(!custom.exists());
-        let factory = TempFileFactory::new(Some(&custom), &output).unwrap();
+        let factory = TempFileFactory::new(std::slice::from_ref(&custom), &output).unwrap();
         assert!(custom.exists());
         factory.create().unwrap();
     }
+
+    #[test]
+    fn stripes_round_robin_and_fails_over_full_directories() {
+        let dir = tempdir().unwrap();
+        let disks = vec![dir.path().join("a"), dir.path().join("b")];
+        let factory = TempFileFactory::new(&disks, &dir.path().join("out.txt")).unwrap();
+
+        let first = factory.create().unwrap();
+        let second = factory.create().unwrap();
+        assert_eq!(first.path().parent(), Some(disks[0].as_path()));
+        assert_eq!(second.path().parent(), Some(disks[1].as_path()));
+
+        // Ao ler de "a", a escrita prefere "b" mesmo sendo a vez de "a".
+        let merged = factory
+            .write_temp(&[disks[0].as_path()], |_| Ok(()))
+            .unwrap();
+        assert_eq!(merged.path().parent(), Some(disks[1].as_path()));
+
+        let mut attempts = 0;
+        let tmp = factory
+            .write_temp(&[], |writer| {
+                attempts += 1;
+                if attempts == 1 {
+                    return Err(io::Error::from(io::ErrorKind::StorageFull).into());
+                }
+                writer.write_all(b"x\n")?;
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(attempts, 2);
+        assert_eq!(tmp.path().parent(), Some(disks[0].as_path()));
+        assert_eq!(factory.full.borrow().as_slice(), &[false, true]);
+        assert_eq!(
+            factory.create().unwrap().path().parent(),
+            Some(disks[0].as_path())
+        );
+    }
+
+    #[test]
+    fn only_storage_full_marks_a_directory_full() {
+        let dir = tempdir().unwrap();
+        let disks = vec![dir.path().join("a"), dir.path().join("b")];
+        let factory = TempFileFactory::new(&disks, &dir.path().join("out.txt")).unwrap();
+
+        fs::remove_dir(&disks[0]).unwrap();
+        let tmp = factory.create().unwrap();
+        assert_eq!(tmp.path().parent(), Some(disks[1].as_path()));
+        assert_eq!(factory.full.borrow().as_slice(), &[false, false]);
+
+        fs::create_dir(&disks[0]).unwrap();
+        let next = [factory.create().unwrap(), factory.create().unwrap()];
+        assert!(next
+            .iter()
+            .any(|tmp| tmp.path().parent() == Some(disks[0].as_path())));
+    }
+
+    #[test]
+    fn stops_only_when_every_directory_is_below_the_reserve() {
+        let dir = tempdir().unwrap();
+        let disks = vec![dir.path().join("a"), dir.path().join("b")];
+        let factory = TempFileFactory::new(&disks, &dir.path().join("out.txt")).unwrap();
+        let factory = factory.with_space_guard(SpaceGuard::new(disks.clone(), None, 0));
+
+        // "a" já saiu da rotação; "b" continua recebendo temporários.
+        factory.full.borrow_mut()[0] = true;
+        let tmp = factory.create().unwrap();
+        assert_eq!(tmp.path().parent(), Some(disks[1].as_path()));
+
+        factory.full.borrow_mut()[1] = true;
+        let err = factory.create().unwrap_err();
+        assert!(err.to_string().contains("todos os diretórios"), "{err}");
+    }
 }
@@ -0,0 +1,385 @@
+use crate::config::Config;
+use crate::control::{Cancelled, RunControl};
+use crate::output::AtomicOutput;
+use crate::progress::ProgressReporter;
+use crate::scanner::{self, InputFile};
+use crate::summary::RunSummary;
+use crate::OverwritePolicy;
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, UNIX_EPOCH};
+
+const STATE_VERSION: u32 = 1;
+
+pub struct WatchOptions {
+    pub quiet_period: Duration,
+    pub state_file: PathBuf,
+    pub once: bool,
+}
+
+pub fn state_path(output: &Path) -> PathBuf {
+    let mut name = output.as_os_str().to_owned();
+    name.push(".watch.json");
+    PathBuf::from(name)
+}
+
+// Observa o diretório de `config.inputs` e incorpora à saída, em lotes, os arquivos novos ou
+// alterados. Com `once`, processa o que já existe e retorna.
+pub fn watch(mut config: Config, options: &WatchOptions, control: &RunControl) -> Result<()> {
+    let [root] = config.inputs.as_slice() else {
+        return Err(anyhow!("O modo watch observa exatamente um diretório"));
+    };
+    if !root.is_dir() {
+        return Err(anyhow!("{:?} não é um diretório", root));
+    }
+    let root = root.clone();
+    // Cada lote é mesclado com a saída existente; um arquivo ilegível não trava os demais e
+    // volta a ser tentado no lote seguinte.
+    config.incremental = true;
+    config.keep_going = true;
+
+    let mut watcher = Watcher {
+        state: WatchState::load(&options.state_file)?,
+        config,
+        state_file: options.state_file.clone(),
+        control,
+        writing: HashSet::new(),
+    };
+    if options.once {
+        watcher.run_batch()?;
+        return Ok(());
+    }
+    watch_events(&mut watcher, &root, options.quiet_period)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct WatchState {
+    version: u32,
+    files: BTreeMap<String, FileStamp>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct FileStamp {
+    size: u64,
+    modified_secs: u64,
+    modified_nanos: u32,
+}
+
+impl FileStamp {
+    fn of(path: &Path) -> Option<Self> {
+        let meta = fs::metadata(path).ok()?;
+        let modified = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some(Self {
+            size: meta.len(),
+            modified_secs: modified.as_secs(),
+            modified_nanos: modified.subsec_nanos(),
+        })
+    }
+}
+
+impl WatchState {
+    fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self {
+                version: STATE_VERSION,
+                files: BTreeMap::new(),
+            });
+        }
+        let data = fs::read(path)
+            .with_context(|| format!("Não foi possível ler o arquivo de estado {:?}", path))?;
+        let state: Self = serde_json::from_slice(&data)
+            .with_context(|| format!("Arquivo de estado {:?} inválido", path))?;
+        if state.version != STATE_VERSION {
+            return Err(anyhow!(
+                "Arquivo de estado {:?} tem versão {} não suportada",
+                path,
+                state.version
+            ));
+        }
+        Ok(state)
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        let mut out = AtomicOutput::create(path, OverwritePolicy::Force)?;
+        serde_json::to_writer_pretty(&mut out, self)
+            .context("Falha ao serializar o estado do modo watch")?;
+        out.write_all(b"\n")
+            .with_context(|| format!("Falha ao gravar o arquivo de estado {:?}", path))?;
+        out.commit()
+    }
+}
+
+struct Pending {
+    file: InputFile,
+    key: String,
+    stamp: FileStamp,
+}
+
+struct Watcher<'a> {
+    config: Config,
+    state: WatchState,
+    state_file: PathBuf,
+    control: &'a RunControl,
+    // Arquivos abertos para escrita segundo o inotify; ficam para o lote seguinte.
+    writing: HashSet<PathBuf>,
+}
+
+impl Watcher<'_> {
+    // Aplica as regras da varredura ao diretório inteiro e devolve o que ainda não foi
+    // processado com o mesmo tamanho e data de modificação.
+    fn pending(&self) -> Result<Vec<Pending>> {
+        let found = Mutex::new(Vec::new());
+        scanner::scan_inputs(
+            &self.config,
+            &|| self.control.cancellation().is_cancelled(),
+            &|batch| {
+                found.lock().unwrap().extend(batch);
+                true
+            },
+        )?;
+        self.control.cancellation().check()?;
+        let mut files = found.into_inner().unwrap();
+        files.sort();
+
+        let mut pending = Vec::new();
+        for file in files {
+            let source = match &file {
+                InputFile::Path(path) => path,
+                InputFile::Member { archive, .. } => archive,
+                InputFile::Stdin => continue,
+            };
+            if self.writing.contains(source) {
+                continue;
+            }
+            // Arquivos removidos desde a varredura simplesmente ficam de fora.
+            let Some(stamp) = FileStamp::of(source) else {
+                continue;
+            };
+            let key = file.display_path().display().to_string();
+            if self.state.files.get(&key) == Some(&stamp) {
+                continue;
+            }
+            pending.push(Pending { file, key, stamp });
+        }
+        Ok(pending)
+    }
+
+    fn run_batch(&mut self) -> Result<Option<RunSummary>> {
+        let pending = self.pending()?;
+        if pending.is_empty() {
+            return Ok(None);
+        }
+        if !self.config.quiet {
+            eprintln!("Novo lote com {} arquivo(s)", pending.len());
+        }
+
+        let files: Vec<InputFile> = pending.iter().map(|p| p.file.clone()).collect();
+        let mut progress = ProgressReporter::new(!self.config.quiet, files.len());
+        let summary = crate::run_files(&self.config, files, &mut progress, self.control)?;
+
+        // Entradas que falharam não entram no estado e são tentadas de novo no próximo lote.
+        let failed: HashSet<&str> = summary
+            .failed_inputs
+            .iter()
+            .map(|f| f.path.as_str())
+            .collect();
+        for entry in pending {
+            let source = match &entry.file {
+                InputFile::Member { archive, .. } => archive.display().to_string(),
+                _ => entry.key.clone(),
+            };
+            if !failed.contains(entry.key.as_str()) && !failed.contains(source.as_str()) {
+                self.state.files.insert(entry.key, entry.stamp);
+            }
+        }
+        self.state.save(&self.state_file)?;
+
+        if !self.config.quiet {
+            let added = summary.incremental.map_or(0, |i| i.added);
+            eprintln!(
+                "Lote concluído: {} linha(s) nova(s), {} no total em {}",
+                added,
+                summary.unique_lines_written,
+                self.config.output.display()
+            );
+        }
+        Ok(Some(summary))
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn watch_events(watcher: &mut Watcher, root: &Path, quiet_period: Duration) -> Result<()> {
+    use inotify::{EventMask, Inotify, WatchDescriptor, WatchMask};
+    use std::collections::HashMap;
+    use std::io;
+    use std::time::Instant;
+
+    const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+    let mut inotify = Inotify::init().context("Não foi possível iniciar o inotify")?;
+    let mut dirs: HashMap<WatchDescriptor, PathBuf> = HashMap::new();
+    let recursive = watcher.config.recursive;
+    let follow_links = watcher.config.follow_symlinks;
+    // Sem recursão só a raiz é observada; com ela, cada subdiretório (inclusive os criados
+    // depois) recebe seu próprio watch.
+    let add_watches = |inotify: &mut Inotify,
+                       dirs: &mut HashMap<WatchDescriptor, PathBuf>,
+                       dir: &Path|
+     -> Result<()> {
+        let walk = walkdir::WalkDir::new(dir).follow_links(follow_links);
+        let walk = if recursive { walk } else { walk.max_depth(0) };
+        for entry in walk.into_iter().filter_map(|e| e.ok()) {
+            if !entry.file_type().is_dir() {
+                continue;
+            }
+            let mask = WatchMask::CREATE
+                | WatchMask::MODIFY
+                | WatchMask::CLOSE_WRITE
+                | WatchMask::MOVED_TO
+                | WatchMask::MOVED_FROM
+                | WatchMask::DELETE;
+            let wd = inotify
+                .watches()
+                .add(entry.path(), mask)
+                .with_context(|| format!("Não foi possível observar {:?}", entry.path()))?;
+            dirs.insert(wd, entry.path().to_path_buf());
+        }
+        Ok(())
+    };
+    add_watches(&mut inotify, &mut dirs, root)?;
+
+    if !watcher.config.quiet {
+        eprintln!(
+            "Observando {} (lotes após {}s sem atividade)",
+            root.display(),
+            quiet_period.as_secs_f64()
+        );
+    }
+
+    // Arquivos já presentes na partida entram no primeiro lote, após o período de silêncio.
+    let mut last_activity = Some(Instant::now());
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        watcher.control.cancellation().check()?;
+        let events: Vec<(WatchDescriptor, EventMask, Option<PathBuf>)> =
+            match inotify.read_events(&mut buffer) {
+                Ok(events) => events
+                    .map(|e| (e.wd, e.mask, e.name.map(PathBuf::from)))
+                    .collect(),
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => Vec::new(),
+                Err(err) => return Err(err).context("Erro ao ler eventos do inotify"),
+            };
+        let idle = events.is_empty();
+
+        for (wd, mask, name) in events {
+            if mask.contains(EventMask::Q_OVERFLOW) {
+                // Eventos perdidos: a próxima varredura decide o que é novo.
+                watcher.writing.clear();
+                last_activity = Some(Instant::now());
+                continue;
+            }
+            if mask.contains(EventMask::IGNORED) {
+                dirs.remove(&wd);
+                continue;
+            }
+            let (Some(dir), Some(name)) = (dirs.get(&wd), name) else {
+                continue;
+            };
+            let path = dir.join(name);
+            last_activity = Some(Instant::now());
+            if mask.contains(EventMask::ISDIR) {
+                if recursive && mask.intersects(EventMask::CREATE | EventMask::MOVED_TO) {
+                    add_watches(&mut inotify, &mut dirs, &path)?;
+                }
+            } else if mask.intersects(EventMask::CREATE | EventMask::MODIFY) {
+                watcher.writing.insert(path);
+            } else {
+                watcher.writing.remove(&path);
+            }
+        }
+
+        if last_activity.is_some_and(|at| at.elapsed() >= quiet_period) {
+            last_activity = None;
+            match watcher.run_batch() {
+                Ok(_) => {}
+                Err(err) if err.downcast_ref::<Cancelled>().is_some() => return Err(err),
+                // O lote é refeito na próxima atividade do diretório.
+                Err(err) => eprintln!("Falha ao processar o lote: {:#}", err),
+            }
+        }
+        if idle {
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn watch_events(_watcher: &mut Watcher, _root: &Path, _quiet_period: Duration) -> Result<()> {
+    Err(anyhow!(
+        "A observação contínua usa inotify e só está disponível no Linux; use --once"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn build_config(root: PathBuf, output: PathBuf) -> Config {
+        Config {
+            output,
+            inputs: vec![root],
+            keep_going: true,
+            recursive: true,
+            chunk_lines: 10,
+            quiet: true,
+            incremental: true,
+            min_free_bytes: 0,
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn merges_new_files_incrementally_and_remembers_them() {
+        let dir = tempdir().unwrap();
+        let drop = dir.path().join("drop");
+        fs::create_dir(&drop).unwrap();
+        fs::write(drop.join("a.txt"), b"c\na\n").unwrap();
+        let output = dir.path().join("out.txt");
+        let state_file = state_path(&output);
+        let control = RunControl::new();
+        let new_watcher = || Watcher {
+            config: build_config(drop.clone(), output.clone()),
+            state: WatchState::load(&state_file).unwrap(),
+            state_file: state_file.clone(),
+            control: &control,
+            writing: HashSet::new(),
+        };
+
+        let mut watcher = new_watcher();
+        watcher.run_batch().unwrap().unwrap();
+        assert_eq!(fs::read_to_string(&output).unwrap(), "a\nc\n");
+
+        // Após reiniciar, o estado impede que a.txt seja lido de novo.
+        let mut watcher = new_watcher();
+        assert!(watcher.run_batch().unwrap().is_none());
+
+        fs::write(drop.join("b.txt"), b"b\nc\n").unwrap();
+        watcher.writing.insert(drop.join("b.txt"));
+        assert!(watcher.run_batch().unwrap().is_none());
+        watcher.writing.clear();
+        let summary = watcher.run_batch().unwrap().unwrap();
+        assert_eq!(summary.files_processed, 1);
+        assert_eq!(summary.incremental.unwrap().previous_lines, 2);
+        assert_eq!(summary.incremental.unwrap().added, 1);
+        assert_eq!(fs::read_to_string(&output).unwrap(), "a\nb\nc\n");
+
+        let state = WatchState::load(&state_file).unwrap();
+        assert_eq!(state.files.len(), 2);
+    }
+}